@@ -0,0 +1,67 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use track2kml::{
+    build_kml, Alarm, Classification, Database, Location, Position3d, Track, TrackingRecord,
+    WriteAsKmlOptions,
+};
+
+const CLASSIFICATIONS: [Classification; 4] = [
+    Classification::Uav,
+    Classification::Gcs,
+    Classification::Other,
+    Classification::Unknown,
+];
+
+/// Creates a database with a single track of `record_count` positional records, switching
+/// classification every 1000 records.
+fn large_database(record_count: u64) -> Database {
+    let records = (0..record_count)
+        .map(|idx| TrackingRecord {
+            time: 1_700_000_000_000 + idx * 100,
+            record_number: idx,
+            classification: CLASSIFICATIONS[(idx / 1000) as usize % CLASSIFICATIONS.len()],
+            alarm: Alarm {
+                active: false,
+                certainty: 0.,
+            },
+            location: Location::Position3d(Position3d {
+                lat: 37.4 + idx as f64 * 1e-6,
+                lon: -6.0,
+                height: 100.,
+            }),
+            identification: None,
+            velocity: None,
+            cuas_location: None,
+        })
+        .collect();
+    Database {
+        detection: Vec::new(),
+        tracks: vec![Track {
+            uas_id: 0,
+            name: None,
+            records,
+            uav_home_location: None,
+        }],
+        static_cuas_location: Position3d {
+            lat: 37.4,
+            lon: -6.0,
+            height: 0.,
+        },
+    }
+}
+
+fn classification_split(c: &mut Criterion) {
+    let options = WriteAsKmlOptions::default().cuas_range(100.);
+    for record_count in [10_000, 100_000, 500_000] {
+        let database = large_database(record_count);
+        c.bench_function(&format!("build_kml {} records", record_count), |b| {
+            b.iter(|| build_kml(black_box(&database), &options).unwrap())
+        });
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = classification_split
+}
+criterion_main!(benches);
@@ -1,5 +1,8 @@
+use std::path::PathBuf;
+
 use clap::builder::TypedValueParser;
 use track2kml::Position3d;
+use track2kml_cli::SourceOrigin;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Position3dParser;
@@ -86,3 +89,38 @@ impl TypedValueParser for Position3dParser {
         })
     }
 }
+
+/// Parses a `FILE=LON,LAT[,HEIGHT]` argument, giving the C-UAS location of one of several input
+/// files.
+#[derive(Clone, Copy, Debug)]
+pub struct SourceOriginParser;
+
+impl TypedValueParser for SourceOriginParser {
+    type Value = SourceOrigin;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &std::ffi::OsStr,
+    ) -> Result<Self::Value, clap::Error> {
+        let value = value
+            .to_str()
+            .ok_or_else(|| clap::Error::new(clap::error::ErrorKind::InvalidUtf8).with_cmd(cmd))?;
+        // File names may contain `=`, but positions cannot
+        let (path, position) = value.rsplit_once('=').ok_or_else(|| {
+            let mut err = clap::Error::new(clap::error::ErrorKind::ValueValidation).with_cmd(cmd);
+            err.insert(
+                clap::error::ContextKind::InvalidValue,
+                clap::error::ContextValue::String(
+                    "Must be formatted as FILE=LON,LAT[,HEIGHT]".to_string(),
+                ),
+            );
+            err
+        })?;
+        Ok(SourceOrigin {
+            path: PathBuf::from(path),
+            origin: Position3dParser.parse_ref(cmd, arg, std::ffi::OsStr::new(position))?,
+        })
+    }
+}
@@ -1,73 +1,974 @@
 use clap::ArgMatches;
 use std::{
+    collections::{HashMap, HashSet},
     ffi::OsStr,
-    fs::File,
-    io::{BufReader, BufWriter},
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Seek, Write},
     path::{Path, PathBuf},
 };
 
-use colored::Colorize;
+use colored::{ColoredString, Colorize};
+use rayon::prelude::*;
 use textwrap::Options;
+use walkdir::WalkDir;
 
-use courageous_format::{Document, Position3d};
-use track2kml::{write_as_kml, WriteAsKmlOptions};
+use courageous_format::Position3d;
+use track2kml::{
+    build_kml, build_many_kml, cot_events, decompress,
+    kml::{skipped_records_folder, Feature},
+    read_art_logs, read_datcon_track, read_document, read_document_lenient,
+    read_document_lenient_reopening, read_document_reopening, read_hgh_log, read_record_stream,
+    read_robin_radar_gpx, read_senhive_log, stream_as_kml_reopening, write_as_cot,
+    write_as_courageous, write_as_csv, write_as_czml, write_as_geojson, write_as_gpx, write_as_kml,
+    write_as_kmz, write_cot_event, write_kml, write_kmz, SchemaVersion, SkippedRecord,
+    WriteAsCotOptions, WriteAsCsvOptions, WriteAsCzmlOptions, WriteAsGeoJsonOptions,
+    WriteAsKmlOptions,
+};
+
+/// The C-UAS location given for one of the files of the `merge` command.
+#[derive(Clone, Debug)]
+pub struct SourceOrigin {
+    pub path: PathBuf,
+    pub origin: Position3d,
+}
+
+/// Path that stands for stdin when given as input, and stdout when given as output.
+pub const STDIO_PATH: &str = "-";
+
+/// Number of skipped records listed by `--lenient`, out of all of them.
+const MAX_LISTED_SKIPPED_RECORDS: usize = 10;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// KML, for Google Earth.
+    #[default]
+    Kml,
+    /// KML with all icons bundled, viewable without an internet connection.
+    Kmz,
+    /// GeoJSON FeatureCollection.
+    #[value(name = "geojson")]
+    GeoJson,
+    /// CZML, for time-dynamic replay on Cesium.
+    Czml,
+    /// GPX 1.1. Only absolute positions are converted.
+    Gpx,
+    /// Cursor-on-Target events, loadable on ATAK/WinTAK. Only track positions are converted.
+    Cot,
+    /// A table with one row per record.
+    Csv,
+    /// Validated COURAGEOUS JSON document, for archiving files of other formats.
+    Courageous,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum InputFormat {
+    /// COURAGEOUS JSON document.
+    Courageous,
+    /// COURAGEOUS records logged one per line, after a header line with the C-UAS location.
+    CourageousJsonl,
+    /// GPX file exported by Robin Radar systems. Requires `--origin`.
+    RobinRadar,
+    /// Detection log of a Senhive sensor network, as JSON Lines. Requires `--origin`.
+    Senhive,
+    /// Track log of an HGH infrared camera, of either iteration of the format. `--origin` overrides
+    /// the camera position given by the log.
+    Hgh,
+    /// Tracking, detection or identification log of an ART system, as CSV. Other logs of the
+    /// same system can be given with `--art-log`. Requires `--origin`.
+    Art,
+}
+
+impl InputFormat {
+    /// Guesses the format of a file from its extension.
+    pub fn from_path(path: &Path) -> InputFormat {
+        match uncompressed_path(path).extension().and_then(OsStr::to_str) {
+            Some("gpx") => InputFormat::RobinRadar,
+            Some("jsonl") => InputFormat::CourageousJsonl,
+            _ => InputFormat::Courageous,
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Guesses the format of a file from its extension.
+    pub fn from_path(path: &Path) -> Option<OutputFormat> {
+        match path.extension().and_then(OsStr::to_str)? {
+            "kml" => Some(OutputFormat::Kml),
+            "kmz" => Some(OutputFormat::Kmz),
+            "geojson" => Some(OutputFormat::GeoJson),
+            "czml" => Some(OutputFormat::Czml),
+            "gpx" => Some(OutputFormat::Gpx),
+            "cot" => Some(OutputFormat::Cot),
+            "csv" => Some(OutputFormat::Csv),
+            "json" => Some(OutputFormat::Courageous),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Kml => "kml",
+            OutputFormat::Kmz => "kmz",
+            OutputFormat::GeoJson => "geojson",
+            OutputFormat::Czml => "czml",
+            OutputFormat::Gpx => "gpx",
+            OutputFormat::Cot => "cot",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Courageous => "json",
+        }
+    }
+}
+
+/// A file to convert, found by [`collect_inputs`].
+pub struct InputFile {
+    pub path: PathBuf,
+    /// Path of the file relative to the directory or glob pattern it was found with, used to mirror
+    /// the directory structure of the input files into the output directory.
+    pub relative_path: PathBuf,
+}
+
+/// The result of converting one of the files given to [`process_batch`].
+pub struct BatchResult {
+    pub input_path: PathBuf,
+    /// The path of the output file, or why the file could not be converted.
+    pub result: Result<PathBuf, anyhow::Error>,
+}
+
+/// Returns whether `args` describe several files to convert instead of a single one, i.e. several
+/// input paths, a directory or a glob pattern were given.
+pub fn is_batch(args: &ArgMatches) -> bool {
+    let mut input_paths = args.get_many::<PathBuf>("input_paths").unwrap();
+    input_paths.len() > 1 || input_paths.any(|path| path.is_dir() || is_glob(path))
+}
+
+/// Converts the input file as described by `args`, returning the path of the output file, or
+/// `None` if it was written to stdout.
+pub fn process_input(args: &ArgMatches) -> Result<Option<PathBuf>, anyhow::Error> {
+    let input_path = args
+        .get_many::<PathBuf>("input_paths")
+        .unwrap()
+        .next()
+        .unwrap();
+    let output_arg = args.get_one::<PathBuf>("output");
+    let format = args
+        .get_one::<OutputFormat>("format")
+        .copied()
+        .or_else(|| output_arg.and_then(|path| OutputFormat::from_path(path)))
+        .unwrap_or_default();
+
+    // Reading from stdin writes into stdout unless an output file is given
+    let output = match output_arg {
+        Some(path) if path.as_os_str() == STDIO_PATH => Output::Stdout,
+        Some(path) => Output::File(path.clone()),
+        None if input_path.as_os_str() == STDIO_PATH => Output::Stdout,
+        None => Output::File(default_output_path(
+            input_path,
+            format,
+            args.get_flag("cot_dir"),
+        )),
+    };
+
+    convert(args, input_path, output, format, false)
+}
+
+/// Converts all the files found in the input paths of `args` (See [`collect_inputs`]) in parallel.
+///
+/// If an output directory is given, the directory structure of the input files is mirrored into
+/// it. Otherwise, each output file is written next to its input file. Files that cannot be
+/// converted do not stop the others from being converted, and their errors are returned instead.
+pub fn process_batch(args: &ArgMatches) -> Result<Vec<BatchResult>, anyhow::Error> {
+    let input_paths: Vec<PathBuf> = args
+        .get_many::<PathBuf>("input_paths")
+        .unwrap()
+        .cloned()
+        .collect();
+    if input_paths
+        .iter()
+        .any(|path| path.as_os_str() == STDIO_PATH)
+    {
+        anyhow::bail!("stdin cannot be read when converting several files");
+    }
+    let output_dir = args.get_one::<PathBuf>("output");
+    if output_dir.is_some_and(|path| path.as_os_str() == STDIO_PATH) {
+        anyhow::bail!("Several files cannot be written into stdout");
+    }
+    let format = args
+        .get_one::<OutputFormat>("format")
+        .copied()
+        .unwrap_or_default();
+    let cot_dir = args.get_flag("cot_dir");
+
+    let inputs = collect_inputs(&input_paths)?;
+    if inputs.is_empty() {
+        anyhow::bail!("No files to convert were found");
+    }
+    if inputs.len() > 1 && args.get_many::<PathBuf>("ground_truth").is_some() {
+        // The same flight would be added to every file
+        anyhow::bail!("--ground-truth can only be used when converting a single file");
+    }
+    if inputs.len() > 1 && args.get_many::<PathBuf>("art_log").is_some() {
+        anyhow::bail!("--art-log can only be used when converting a single file");
+    }
+    let output_paths: Vec<PathBuf> = inputs
+        .iter()
+        .map(|input| match output_dir {
+            Some(output_dir) => {
+                default_output_path(&output_dir.join(&input.relative_path), format, cot_dir)
+            }
+            None => default_output_path(&input.path, format, cot_dir),
+        })
+        .collect();
+    let mut converted_into = HashMap::new();
+    for (input, output_path) in inputs.iter().zip(&output_paths) {
+        if let Some(other_path) = converted_into.insert(output_path, &input.path) {
+            anyhow::bail!(
+                "Both {:?} and {:?} would be converted into {:?}",
+                other_path,
+                input.path,
+                output_path
+            );
+        }
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.get_one::<usize>("jobs").copied().unwrap_or_default())
+        .build()?;
+    Ok(pool.install(|| {
+        inputs
+            .par_iter()
+            .zip(output_paths)
+            .map(|(input, output_path)| BatchResult {
+                input_path: input.path.clone(),
+                result: convert_batch_file(args, &input.path, output_path, format),
+            })
+            .collect()
+    }))
+}
+
+/// Merges the COURAGEOUS files given to the `merge` command into a single KML or KMZ file, with a
+/// folder per file (See [`build_many_kml`]). Returns the path of the output file, or `None` if it
+/// was written to stdout.
+pub fn process_merge(args: &ArgMatches) -> Result<Option<PathBuf>, anyhow::Error> {
+    let input_paths: Vec<&PathBuf> = args.get_many("input_paths").unwrap().collect();
+    let output_path: &PathBuf = args.get_one("output").unwrap();
+    let format = args
+        .get_one::<OutputFormat>("format")
+        .copied()
+        .or_else(|| OutputFormat::from_path(output_path))
+        .unwrap_or_default();
+    let output = match output_path {
+        path if path.as_os_str() == STDIO_PATH => Output::Stdout,
+        path => Output::File(path.clone()),
+    };
+
+    if !matches!(format, OutputFormat::Kml | OutputFormat::Kmz) {
+        anyhow::bail!("Files can only be merged into KML or KMZ");
+    }
+    if let Output::File(output_path) = &output {
+        if input_paths
+            .iter()
+            .any(|input_path| is_same_file(output_path, input_path))
+        {
+            anyhow::bail!("The output file would overwrite one of the input files");
+        }
+        if output_path.exists() && !args.get_flag("force") {
+            anyhow::bail!(
+                "The output file {:?} already exists. Use --force to overwrite it",
+                output_path
+            );
+        }
+    }
+
+    let origins: Vec<&SourceOrigin> = args
+        .get_many::<SourceOrigin>("origins")
+        .into_iter()
+        .flatten()
+        .collect();
+    if let Some(origin) = origins.iter().find(|origin| {
+        !input_paths
+            .iter()
+            .any(|input_path| is_same_path(&origin.path, input_path))
+    }) {
+        anyhow::bail!(
+            "--origin was given for {:?}, which is not one of the input files",
+            origin.path
+        );
+    }
+
+    let sources = input_paths
+        .into_iter()
+        .map(|path| {
+            let name = uncompressed_path(path)
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            let origin = origins
+                .iter()
+                .find(|origin| is_same_path(&origin.path, path))
+                .map(|origin| origin.origin);
+            Ok((name, read_merge_input(path, origin)?))
+        })
+        .collect::<Result<Vec<_>, anyhow::Error>>()?;
+    let document = build_many_kml(
+        &sources,
+        &WriteAsKmlOptions::default()
+            .disable_track_icons(args.get_flag("no_track_icons"))
+            .cuas_range(*args.get_one("cuas_range").unwrap_or(&100.)),
+    )?;
+    match format {
+        OutputFormat::Kmz => output.write_seekable(|writer| write_kmz(&document, writer))?,
+        _ => output.write(|writer| write_kml(&document, writer))?,
+    }
+
+    Ok(output.into_path())
+}
+
+/// Reads one of the files given to the `merge` command, using `origin` as its C-UAS location if
+/// given. Files that do not store their C-UAS location can only be merged with one, since every
+/// file needs its own.
+fn read_merge_input(
+    input_path: &Path,
+    origin: Option<Position3d>,
+) -> Result<track2kml::Database, anyhow::Error> {
+    if input_path.as_os_str() == STDIO_PATH {
+        anyhow::bail!("stdin cannot be read when merging files");
+    }
+    let mut database = match InputFormat::from_path(input_path) {
+        InputFormat::Courageous => {
+            let (database, version) = read_document_reopening(|| Ok(open_input(input_path)?))
+                .map_err(|err| match err {
+                    track2kml::Error::InvalidDocument(_) => {
+                        anyhow::anyhow!("Could not load {:?}.\n{}", input_path, err)
+                    }
+                    err => err.into(),
+                })?;
+            if version < SchemaVersion::CURRENT && origin.is_none() {
+                anyhow::bail!(
+                    "COURAGEOUS {} files do not store the C-UAS location. Give the one of {:?} \
+                    with --origin {}=<LON,LAT[,HEIGHT]>",
+                    version,
+                    input_path,
+                    input_path.display()
+                );
+            }
+            database
+        }
+        InputFormat::CourageousJsonl => {
+            let stream = read_record_stream(open_input(input_path)?)?;
+            if stream.truncated {
+                print_message(
+                    "Warning".yellow(),
+                    Some(input_path),
+                    "The last line of the input file is incomplete, and was ignored.",
+                );
+            }
+            stream.database
+        }
+        InputFormat::RobinRadar => {
+            let origin = origin.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Robin Radar GPX files do not store the location of the radar. Give the one of \
+                    {:?} with --origin {}=<LON,LAT[,HEIGHT]>",
+                    input_path,
+                    input_path.display()
+                )
+            })?;
+            read_robin_radar_gpx(open_input(input_path)?, origin)?
+        }
+        InputFormat::Senhive | InputFormat::Hgh | InputFormat::Art => {
+            unreachable!("never guessed from the extension")
+        }
+    };
+    if let Some(origin) = origin {
+        database.static_cuas_location = origin;
+    }
+    Ok(database)
+}
+
+/// Expands the input paths given to the CLI into the files they refer to, in order and without
+/// duplicates.
+///
+/// Directories are searched recursively for files that can be read (COURAGEOUS documents and
+/// record streams, and Robin Radar GPX files, possibly compressed), and glob patterns are expanded
+/// with the `glob` crate. Paths of other files are kept as is.
+pub fn collect_inputs(input_paths: &[PathBuf]) -> Result<Vec<InputFile>, anyhow::Error> {
+    let mut inputs = Vec::new();
+    for path in input_paths {
+        if path.is_dir() {
+            // Keep the name of the directory, so that several ones do not get mixed up. `.` and
+            // `..` have none
+            let base = match path.file_name() {
+                Some(_) => path.parent().unwrap_or(path),
+                None => path,
+            };
+            collect_directory(path, base, &mut inputs)?;
+        } else if is_glob(path) {
+            let pattern = path
+                .to_str()
+                .ok_or_else(|| anyhow::anyhow!("Invalid glob pattern {:?}", path))?;
+            let base: PathBuf = path
+                .components()
+                .take_while(|component| !has_glob_chars(component.as_os_str()))
+                .collect();
+            let mut matched = false;
+            for entry in glob::glob(pattern)? {
+                let entry = entry?;
+                matched = true;
+                if entry.is_dir() {
+                    collect_directory(&entry, &base, &mut inputs)?;
+                } else {
+                    inputs.push(InputFile {
+                        relative_path: relative_path(&entry, &base),
+                        path: entry,
+                    });
+                }
+            }
+            if !matched {
+                anyhow::bail!("No files match {:?}", path);
+            }
+        } else {
+            inputs.push(InputFile {
+                relative_path: path.file_name().map(PathBuf::from).unwrap_or_default(),
+                path: path.clone(),
+            });
+        }
+    }
+
+    // The same file may be found with several paths
+    let mut found = HashSet::new();
+    inputs.retain(|input| found.insert(input.path.clone()));
+    Ok(inputs)
+}
+
+/// Adds the files that can be read inside `dir` into `inputs`, relative to `base`.
+fn collect_directory(
+    dir: &Path,
+    base: &Path,
+    inputs: &mut Vec<InputFile>,
+) -> Result<(), anyhow::Error> {
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry?;
+        let is_input = matches!(
+            uncompressed_path(entry.path())
+                .extension()
+                .and_then(OsStr::to_str),
+            Some("json" | "jsonl" | "gpx")
+        );
+        if entry.file_type().is_file() && is_input {
+            inputs.push(InputFile {
+                relative_path: relative_path(entry.path(), base),
+                path: entry.into_path(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    path.strip_prefix(base).unwrap_or(path).to_owned()
+}
 
-mod clap_util;
+/// Returns whether `path` is a glob pattern rather than the path of an existing file.
+fn is_glob(path: &Path) -> bool {
+    !path.exists() && has_glob_chars(path.as_os_str())
+}
+
+fn has_glob_chars(path: &OsStr) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// Returns where the file at `input_path` is converted into if no output path is given: The same
+/// path with the extension of `format`, or a directory ending in `_cot` if `cot_dir` is set.
+fn default_output_path(input_path: &Path, format: OutputFormat, cot_dir: bool) -> PathBuf {
+    if cot_dir {
+        let mut dir_name = uncompressed_path(input_path)
+            .file_stem()
+            .unwrap_or_default()
+            .to_owned();
+        dir_name.push("_cot");
+        input_path.with_file_name(dir_name)
+    } else {
+        uncompressed_path(input_path).with_extension(format.extension())
+    }
+}
+
+fn convert_batch_file(
+    args: &ArgMatches,
+    input_path: &Path,
+    output_path: PathBuf,
+    format: OutputFormat,
+) -> Result<PathBuf, anyhow::Error> {
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let output_path = convert(args, input_path, Output::File(output_path), format, true)?;
+    Ok(output_path.expect("batches are not written into stdout"))
+}
 
-pub fn process_to_kml(args: &ArgMatches) -> Result<PathBuf, anyhow::Error> {
-    let input_path: &PathBuf = args.get_one("input_path").unwrap();
-    let database = read_input_file(args, input_path)?;
-    let output_path = input_path.with_extension("kml");
-    let output_file = BufWriter::new(File::create(&output_path)?);
-    let disable_track_icons = args.get_flag("no_track_icons");
+/// Converts the file at `input_path` into `output`. `batch` is set when other files are being
+/// converted at the same time, so that messages tell which file they are about.
+fn convert(
+    args: &ArgMatches,
+    input_path: &Path,
+    output: Output,
+    format: OutputFormat,
+    batch: bool,
+) -> Result<Option<PathBuf>, anyhow::Error> {
     let cuas_range = *args
         .try_get_one("cuas_range")
         .ok()
         .flatten()
         .unwrap_or(&100.);
 
-    write_as_kml(
-        database,
-        output_file,
-        WriteAsKmlOptions::default()
-            .disable_track_icons(disable_track_icons)
-            .cuas_range(cuas_range),
-    )?;
+    let input_format = args
+        .get_one::<InputFormat>("input_format")
+        .copied()
+        .unwrap_or_else(|| InputFormat::from_path(input_path));
+
+    if args.get_flag("stream")
+        && (format != OutputFormat::Kml || input_format != InputFormat::Courageous)
+    {
+        anyhow::bail!("--stream can only be used to convert COURAGEOUS files to KML");
+    }
+    if args.get_flag("stream")
+        && input_path.as_os_str() == STDIO_PATH
+        && args.get_one::<Position3d>("detection_origin").is_none()
+    {
+        // Without it, the input would have to be read twice
+        anyhow::bail!("--stream requires --origin when reading from stdin");
+    }
+    if args.get_flag("stream") && args.get_many::<PathBuf>("ground_truth").is_some() {
+        anyhow::bail!("--ground-truth cannot be used with --stream");
+    }
+    if args.get_many::<PathBuf>("art_log").is_some() && input_format != InputFormat::Art {
+        anyhow::bail!("--art-log can only be used with --input-format art");
+    }
+    if args.get_flag("lenient")
+        && (args.get_flag("stream") || input_format != InputFormat::Courageous)
+    {
+        anyhow::bail!("--lenient can only be used with COURAGEOUS files, without --stream");
+    }
+    if args.get_flag("mark_skipped") && !matches!(format, OutputFormat::Kml | OutputFormat::Kmz) {
+        anyhow::bail!("--mark-skipped can only be used with the KML and KMZ output formats");
+    }
+    if args.get_flag("cot_dir") && format != OutputFormat::Cot {
+        anyhow::bail!("--cot-dir can only be used with the CoT output format");
+    }
+    if let Output::File(output_path) = &output {
+        if is_same_file(output_path, input_path) {
+            anyhow::bail!("The output file would overwrite the input file");
+        }
+        if output_path.exists() && !args.get_flag("force") {
+            anyhow::bail!(
+                "The output file {:?} already exists. Use --force to overwrite it",
+                output_path
+            );
+        }
+    }
+    if format == OutputFormat::Cot {
+        return process_to_cot(args, input_path, input_format, output, batch);
+    }
+
+    let options = WriteAsKmlOptions::default()
+        .disable_track_icons(args.get_flag("no_track_icons"))
+        .cuas_range(cuas_range)
+        // ART detections only give angles
+        .angle_points(input_format == InputFormat::Art);
+
+    if args.get_flag("stream") {
+        output.write(|writer| {
+            stream_as_kml_reopening(
+                || Ok(decompress(open_input(input_path)?)?),
+                writer,
+                options
+                    .static_cuas_location(args.get_one::<Position3d>("detection_origin").copied()),
+            )
+        })?;
+        return Ok(output.into_path());
+    }
+
+    let (database, skipped) = read_input_file(args, input_path, input_format, batch)?;
+    if args.get_flag("mark_skipped") {
+        let mut document = build_kml(&database, &options)?;
+        document
+            .features
+            .push(Feature::Folder(skipped_records_folder(
+                &skipped,
+                database.static_cuas_location,
+            )));
+        match format {
+            OutputFormat::Kmz => output.write_seekable(|writer| write_kmz(&document, writer))?,
+            _ => output.write(|writer| write_kml(&document, writer))?,
+        }
+        return Ok(output.into_path());
+    }
+    match format {
+        OutputFormat::Kml => output.write(|writer| write_as_kml(database, writer, options))?,
+        OutputFormat::Kmz => {
+            output.write_seekable(|writer| write_as_kmz(database, writer, options))?
+        }
+        OutputFormat::GeoJson => output.write(|writer| {
+            write_as_geojson(
+                database,
+                writer,
+                WriteAsGeoJsonOptions::default().cuas_range(cuas_range),
+            )
+        })?,
+        OutputFormat::Czml => output.write(|writer| {
+            write_as_czml(
+                database,
+                writer,
+                WriteAsCzmlOptions::default().cuas_range(cuas_range),
+            )
+        })?,
+        OutputFormat::Gpx => output.write(|writer| write_as_gpx(database, writer))?,
+        OutputFormat::Csv => {
+            output.write(|writer| write_as_csv(database, writer, WriteAsCsvOptions::default()))?
+        }
+        OutputFormat::Courageous => output.write(|writer| write_as_courageous(database, writer))?,
+        OutputFormat::Cot => unreachable!("handled above"),
+    }
 
-    Ok(output_path)
+    Ok(output.into_path())
 }
 
-fn read_input_file(
+fn process_to_cot(
     args: &ArgMatches,
     input_path: &Path,
-) -> Result<track2kml::Database, anyhow::Error> {
-    if input_path.extension().as_ref() != Some(&OsStr::new("json")) {
-        textwrap::wrap(
-            &format!("Unexpected file extention. Expected JSON file.",),
-            Options::new(80)
-                .initial_indent(&format!("{}{} ", "Warning".yellow().bold(), ":".bold()))
-                .subsequent_indent("    "),
-        )
-        .into_iter()
-        .for_each(|line| println!("{}", line));
+    input_format: InputFormat,
+    output: Output,
+    batch: bool,
+) -> Result<Option<PathBuf>, anyhow::Error> {
+    let (database, _) = read_input_file(args, input_path, input_format, batch)?;
+    let options = WriteAsCotOptions::default();
+
+    if args.get_flag("cot_dir") {
+        let Output::File(output_path) = output else {
+            anyhow::bail!("--cot-dir cannot be used when writing to stdout");
+        };
+        fs::create_dir_all(&output_path)?;
+        for event in cot_events(&database, &options)? {
+            // Tracks may share a UAS ID, and so a UID
+            let file_name = format!(
+                "{}-track{}-{}.cot",
+                event.uid, event.track, event.record_number
+            );
+            let output_file = BufWriter::new(File::create(output_path.join(file_name))?);
+            write_cot_event(&event, output_file)?;
+        }
+        Ok(Some(output_path))
+    } else {
+        output.write(|writer| write_as_cot(database, writer, options))?;
+        Ok(output.into_path())
+    }
+}
+
+/// Where the converted file is written.
+enum Output {
+    File(PathBuf),
+    Stdout,
+}
+
+trait WriteSeek: Write + Seek {}
+impl<T: Write + Seek> WriteSeek for T {}
+
+impl Output {
+    fn write(
+        &self,
+        f: impl FnOnce(&mut dyn Write) -> Result<(), track2kml::Error>,
+    ) -> Result<(), anyhow::Error> {
+        let mut writer: Box<dyn Write> = match self {
+            Output::File(path) => Box::new(BufWriter::new(File::create(path)?)),
+            Output::Stdout => Box::new(BufWriter::new(io::stdout().lock())),
+        };
+        f(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Like [`Output::write`], for formats that need to seek while being written. Those are written
+    /// into memory first when writing to stdout.
+    fn write_seekable(
+        &self,
+        f: impl FnOnce(&mut dyn WriteSeek) -> Result<(), track2kml::Error>,
+    ) -> Result<(), anyhow::Error> {
+        match self {
+            Output::File(path) => {
+                let mut writer = BufWriter::new(File::create(path)?);
+                f(&mut writer)?;
+                writer.flush()?;
+            }
+            Output::Stdout => {
+                let mut buffer = Cursor::new(Vec::new());
+                f(&mut buffer)?;
+                io::stdout().lock().write_all(buffer.get_ref())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn into_path(self) -> Option<PathBuf> {
+        match self {
+            Output::File(path) => Some(path),
+            Output::Stdout => None,
+        }
     }
+}
+
+/// Returns whether `output` refers to the same file as `input`, even through a different path
+/// (e.g. `./recording.json` and `recording.json`, or a symbolic link). `output` may not exist yet.
+fn is_same_file(output: &Path, input: &Path) -> bool {
+    match (canonical_output_path(output), input.canonicalize()) {
+        (Some(output), Ok(input)) => output == input,
+        _ => false,
+    }
+}
+
+/// Returns whether `a` and `b` refer to the same existing file, or are the same path otherwise.
+fn is_same_path(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Canonicalizes `path`, or its parent directory followed by its file name if it does not exist.
+fn canonical_output_path(path: &Path) -> Option<PathBuf> {
+    if let Ok(path) = path.canonicalize() {
+        return Some(path);
+    }
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Some(parent.canonicalize().ok()?.join(path.file_name()?))
+}
+
+/// Opens the file at `path` for reading, or stdin if it is [`STDIO_PATH`].
+fn open_input(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    Ok(if path.as_os_str() == STDIO_PATH {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(File::open(path)?))
+    })
+}
 
-    let file = File::open(input_path)?;
-    let reader = BufReader::new(file);
-    let parser: Result<Document, anyhow::Error> =
-        serde_json::from_reader(reader).map_err(anyhow::Error::from);
-    match parser {
-        Ok(mut database) => {
+fn read_input_file(
+    args: &ArgMatches,
+    input_path: &Path,
+    input_format: InputFormat,
+    batch: bool,
+) -> Result<(track2kml::Database, Vec<SkippedRecord>), anyhow::Error> {
+    let (mut database, skipped) = match input_format {
+        InputFormat::Courageous => read_courageous_file(args, input_path, batch)?,
+        InputFormat::RobinRadar => {
+            let origin = args
+                .get_one::<Position3d>("detection_origin")
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Robin Radar GPX files do not store the location of the radar, so it \
+                        must be given with --origin"
+                    )
+                })?;
+            (
+                read_robin_radar_gpx(open_input(input_path)?, *origin)?,
+                Vec::new(),
+            )
+        }
+        InputFormat::Senhive => {
+            let origin = args
+                .get_one::<Position3d>("detection_origin")
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Senhive logs do not store the location of the sensors, so it must be \
+                        given with --origin"
+                    )
+                })?;
+            (
+                read_senhive_log(open_input(input_path)?, *origin)?,
+                Vec::new(),
+            )
+        }
+        InputFormat::Art => {
+            let origin = args
+                .get_one::<Position3d>("detection_origin")
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "ART logs do not store the location of the system, so it must be given \
+                        with --origin"
+                    )
+                })?;
+            let logs = std::iter::once(input_path)
+                .chain(
+                    args.get_many::<PathBuf>("art_log")
+                        .into_iter()
+                        .flatten()
+                        .map(PathBuf::as_path),
+                )
+                .map(open_input)
+                .collect::<Result<Vec<_>, _>>()?;
+            (read_art_logs(logs, *origin)?, Vec::new())
+        }
+        InputFormat::Hgh => (
+            read_hgh_log(
+                open_input(input_path)?,
+                args.get_one::<Position3d>("detection_origin").copied(),
+            )?,
+            Vec::new(),
+        ),
+        InputFormat::CourageousJsonl => {
+            let stream = read_record_stream(open_input(input_path)?)?;
+            if stream.truncated {
+                print_message(
+                    "Warning".yellow(),
+                    batch.then_some(input_path),
+                    "The last line of the input file is incomplete, and was ignored.",
+                );
+            }
+            let mut database = stream.database;
             if let Some(origin) = args.get_one::<Position3d>("detection_origin") {
                 database.static_cuas_location = *origin;
-            };
-            return Ok(database);
+            }
+            (database, Vec::new())
+        }
+    };
+
+    for path in args
+        .get_many::<PathBuf>("ground_truth")
+        .into_iter()
+        .flatten()
+    {
+        // Avoid clashing with the UAS IDs of the input file
+        let uas_id = database
+            .tracks
+            .iter()
+            .map(|track| track.uas_id + 1)
+            .max()
+            .unwrap_or(0);
+        let track = read_datcon_track(BufReader::new(File::open(path)?), uas_id)
+            .map_err(|err| anyhow::anyhow!("Could not load ground truth {:?}: {}", path, err))?;
+        database.tracks.push(track);
+    }
+
+    Ok((database, skipped))
+}
+
+/// Reads a COURAGEOUS file, returning the records skipped if `--lenient` was given.
+fn read_courageous_file(
+    args: &ArgMatches,
+    input_path: &Path,
+    batch: bool,
+) -> Result<(track2kml::Database, Vec<SkippedRecord>), anyhow::Error> {
+    let file = batch.then_some(input_path);
+    if input_path.as_os_str() != STDIO_PATH
+        && uncompressed_path(input_path).extension() != Some(OsStr::new("json"))
+    {
+        print_message(
+            "Warning".yellow(),
+            file,
+            "Unexpected file extention. Expected JSON file.",
+        );
+    }
+
+    let origin = args.get_one::<Position3d>("detection_origin");
+    let lenient = args.get_flag("lenient");
+    let result = if input_path.as_os_str() == STDIO_PATH {
+        // stdin cannot be read again if the document has to be, so it is kept in memory
+        let mut contents = Vec::new();
+        io::stdin().lock().read_to_end(&mut contents)?;
+        let reader = Cursor::new(contents);
+        if lenient {
+            read_document_lenient(reader)
+                .map(|document| (document.database, document.version, document.skipped))
+        } else {
+            read_document(reader).map(|(database, version)| (database, version, Vec::new()))
+        }
+    } else {
+        let open = || Ok(open_input(input_path)?);
+        if lenient {
+            read_document_lenient_reopening(open)
+                .map(|document| (document.database, document.version, document.skipped))
+        } else {
+            read_document_reopening(open).map(|(database, version)| (database, version, Vec::new()))
+        }
+    };
+    let (mut database, version, skipped) = result.map_err(|err| match err {
+        track2kml::Error::InvalidDocument(_) => {
+            anyhow::anyhow!("Could not load input file.\n{}", err)
+        }
+        err => err.into(),
+    })?;
+
+    if version < SchemaVersion::CURRENT {
+        print_message(
+            "Info".blue(),
+            file,
+            &format!(
+                "Detected COURAGEOUS schema {}, converted into {}.",
+                version,
+                SchemaVersion::CURRENT
+            ),
+        );
+        if origin.is_none() {
+            print_message(
+                "Warning".yellow(),
+                file,
+                &format!(
+                    "COURAGEOUS {} files do not store the C-UAS location. Use --origin to give it.",
+                    version
+                ),
+            );
+        }
+    }
+    if !skipped.is_empty() {
+        // Keep the list together with its message when converting several files at once
+        let _stderr = io::stderr().lock();
+        print_message(
+            "Warning".yellow(),
+            file,
+            &format!("Skipped {} invalid records:", skipped.len()),
+        );
+        for record in skipped.iter().take(MAX_LISTED_SKIPPED_RECORDS) {
+            eprintln!("    - {}", record);
+        }
+        if skipped.len() > MAX_LISTED_SKIPPED_RECORDS {
+            eprintln!(
+                "    ... and {} more",
+                skipped.len() - MAX_LISTED_SKIPPED_RECORDS
+            );
         }
-        Err(err) => Err(anyhow::anyhow!(
-            "Could not load input file.\n\
-        Tried loading it as a {} file, but got the following error: {}",
-            "COURAGEOUS (v0.4)", // TODO: automatically detect COURAGEOUS format version being used
-            err
-        )),
     }
+    if let Some(origin) = origin {
+        database.static_cuas_location = *origin;
+    };
+    Ok((database, skipped))
+}
+
+/// Returns `path` without its `.gz` or `.zst` extension, if it has one.
+fn uncompressed_path(path: &Path) -> PathBuf {
+    match path.extension().and_then(OsStr::to_str) {
+        Some("gz" | "zst") => path.with_extension(""),
+        _ => path.to_owned(),
+    }
+}
+
+/// Prints a message into stderr, so that it can not get mixed with output written to stdout. `file`
+/// is the input file the message is about, shown when converting several files.
+fn print_message(kind: ColoredString, file: Option<&Path>, message: &str) {
+    let message = match file {
+        Some(file) => format!("{}: {}", file.display(), message),
+        None => message.to_owned(),
+    };
+    let mut stderr = io::stderr().lock();
+    textwrap::wrap(
+        &message,
+        Options::new(80)
+            .initial_indent(&format!("{}{} ", kind.bold(), ":".bold()))
+            .subsequent_indent("    "),
+    )
+    .into_iter()
+    .for_each(|line| {
+        let _ = writeln!(stderr, "{}", line);
+    });
 }
@@ -1,12 +1,15 @@
 use std::process::ExitCode;
 
-use clap::{CommandFactory, Parser};
+use clap::{CommandFactory, Parser, Subcommand};
 use colored::Colorize;
 use courageous_format::Position3d;
 use std::path::PathBuf;
 use textwrap::Options;
 
-use track2kml_cli::process_to_kml;
+use track2kml_cli::{
+    is_batch, process_batch, process_input, process_merge, BatchResult, InputFormat, OutputFormat,
+    SourceOrigin,
+};
 
 mod clap_util;
 
@@ -15,9 +18,102 @@ fn main() -> ExitCode {
 
     #[derive(Parser)]
     #[command(author, version, about, long_about = None)]
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    struct Cli {
+        #[command(flatten)]
+        args: Args,
+
+        #[command(subcommand)]
+        command: Option<Command>,
+    }
+
+    #[derive(Subcommand)]
+    enum Command {
+        /// Convert a file into another format. Same as running track2kml without a command.
+        Convert(Args),
+        /// Merge several COURAGEOUS files (e.g. recorded by different C-UAS systems) into a single
+        /// KML or KMZ file, with a folder per file.
+        Merge(MergeArgs),
+    }
+
+    #[derive(clap::Args)]
+    struct MergeArgs {
+        /// The paths of the files to merge: COURAGEOUS files and record streams, or Robin Radar GPX
+        /// files given an `--origin`.
+        #[arg(required = true)]
+        input_paths: Vec<PathBuf>,
+
+        /// The C-UAS location of one of the input files, overwriting the one stored in it. Required
+        /// for files that do not store it, such as COURAGEOUS v0.3 files. Can be given once for
+        /// each input file.
+        ///
+        /// Positions are formatted like the `--origin` of the `convert` command.
+        #[arg(
+            long = "origin",
+            value_name = "FILE=LON,LAT[,HEIGHT]",
+            value_parser = clap_util::SourceOriginParser
+        )]
+        origins: Vec<SourceOrigin>,
+
+        /// The path of the output file, or `-` to write it into stdout.
+        #[arg(long, short = 'o', value_name = "PATH")]
+        output: PathBuf,
+
+        /// Overwrite the output file if it already exists.
+        #[arg(long)]
+        force: bool,
+
+        /// The format of the output file: `kml` or `kmz`. Guessed from the extension of `--output`
+        /// if not given, and KML otherwise.
+        #[arg(long, short = 'f', visible_alias = "to", value_enum)]
+        format: Option<OutputFormat>,
+
+        /// Hide all track icons, and only show their path or ray.
+        #[arg(long)]
+        no_track_icons: bool,
+
+        /// Maximum distance from the C-UAS where objects can be detected, in meters.
+        #[arg(long, short = 'r')]
+        cuas_range: Option<f64>,
+    }
+
+    #[derive(clap::Args)]
     struct Args {
-        /// The path of the file to convert to KML.
-        input_path: PathBuf,
+        /// The paths of the files to convert, or `-` to read a file from stdin.
+        ///
+        /// Directories are searched recursively for files that can be converted, and glob patterns
+        /// (e.g. `'logs/**/*.json'`) are expanded. When several files are given, they are converted
+        /// in parallel and a summary is shown at the end.
+        #[arg(required = true)]
+        input_paths: Vec<PathBuf>,
+
+        /// The path of the output file, or `-` to write it into stdout. Defaults to the input file
+        /// with the extension of the output format, or stdout when reading from stdin.
+        ///
+        /// When converting several files, the directory they are written into, mirroring the
+        /// directory structure of the input files. Defaults to writing each one next to its input.
+        #[arg(long, short = 'o', value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Number of files converted at the same time when converting several files. Defaults to
+        /// the number of CPUs.
+        #[arg(long, short = 'j')]
+        jobs: Option<usize>,
+
+        /// Overwrite the output file if it already exists.
+        #[arg(long)]
+        force: bool,
+
+        /// The format to convert the file into. Guessed from the extension of `--output` if not
+        /// given, and KML otherwise.
+        #[arg(long, short = 'f', visible_alias = "to", value_enum)]
+        format: Option<OutputFormat>,
+
+        /// The format of the input file. Guessed from its extension if not given: `.gpx` files are
+        /// read as Robin Radar GPX files, `.jsonl` files as COURAGEOUS record streams, and
+        /// everything else as COURAGEOUS files. Other formats must always be given.
+        #[arg(long, short = 'i', value_enum)]
+        input_format: Option<InputFormat>,
 
         /// Specify the detection origin (Radar position) in GPS coordinates `lat,lon,height`.
         ///
@@ -26,6 +122,27 @@ fn main() -> ExitCode {
         #[arg(value_enum, long = "origin", value_parser = clap_util::Position3dParser)]
         detection_origin: Option<Position3d>,
 
+        /// CSV file exported by DatCon from a DJI flight log, added to the output as a ground truth
+        /// track. Can be given multiple times, but only when converting a single file.
+        #[arg(long, value_name = "DATCON_CSV")]
+        ground_truth: Vec<PathBuf>,
+
+        /// With `--input-format art`: Another ART log read along with the input file, such as its
+        /// detection or identification log. Can be given multiple times, but only when converting
+        /// a single file.
+        #[arg(long, value_name = "PATH")]
+        art_log: Vec<PathBuf>,
+
+        /// Skip the records of COURAGEOUS files that cannot be read, instead of failing. A summary
+        /// of the skipped records is shown.
+        #[arg(long)]
+        lenient: bool,
+
+        /// When exporting to KML or KMZ with `--lenient`: Add a folder marking where records were
+        /// skipped, at the last known position of their track or detection set.
+        #[arg(long, requires = "lenient")]
+        mark_skipped: bool,
+
         /// When exporting to KML: Hide all track icons, and only show their path or ray.
         #[arg(long)]
         no_track_icons: bool,
@@ -36,16 +153,59 @@ fn main() -> ExitCode {
         /// Arc or Quad.
         #[arg(long, short = 'r')]
         cuas_range: Option<f64>,
+
+        /// When exporting to KML: Convert the input file incrementally instead of loading it
+        /// entirely into memory.
+        ///
+        /// Useful for very large files. Memory usage is then bounded by the largest track or
+        /// detection set in the file. Requires `--origin` when reading from stdin.
+        #[arg(long)]
+        stream: bool,
+
+        /// When exporting to CoT: Write each event into its own file, inside a directory named after
+        /// the input file, instead of writing all of them into a single file.
+        #[arg(long)]
+        cot_dir: bool,
     }
 
-    let cmd = Args::command()
+    let cmd = Cli::command()
         .name("track2kml")
         .help_template(include_str!("help_template"));
 
     let matches = cmd.clone().get_matches();
 
-    match process_to_kml(&matches) {
-        Ok(output_path) => {
+    let result = match matches.subcommand() {
+        Some(("merge", matches)) => process_merge(matches),
+        subcommand => {
+            let matches = match subcommand {
+                Some(("convert", matches)) => matches,
+                _ => &matches,
+            };
+            if is_batch(matches) {
+                return match process_batch(matches) {
+                    Ok(results) => print_summary(&results, start_time),
+                    Err(err) => {
+                        eprintln!("{}{} {}", "Error".red().bold(), ":".bold(), err);
+                        ExitCode::FAILURE
+                    }
+                };
+            }
+            process_input(matches)
+        }
+    };
+
+    match result {
+        Ok(None) => {
+            // The result was written into stdout, so keep it clean
+            eprintln!(
+                "{}{} Finished in {}ms.",
+                "OK".green().bold(),
+                ":".bold(),
+                (std::time::Instant::now() - start_time).as_millis()
+            );
+            ExitCode::SUCCESS
+        }
+        Ok(Some(output_path)) => {
             textwrap::wrap(
                 &format!(
                     "Finished in {}ms.\nSaved result into {:?}",
@@ -67,3 +227,54 @@ fn main() -> ExitCode {
         }
     }
 }
+
+/// Prints whether each file of a batch could be converted, failing if any of them could not.
+fn print_summary(results: &[BatchResult], start_time: std::time::Instant) -> ExitCode {
+    let input_width = results
+        .iter()
+        .map(|result| result.input_path.display().to_string().chars().count())
+        .max()
+        .unwrap_or(0);
+    let failed = results
+        .iter()
+        .filter(|result| result.result.is_err())
+        .count();
+
+    println!();
+    for result in results {
+        let input_path = result.input_path.display().to_string();
+        match &result.result {
+            Ok(output_path) => println!(
+                "{} {:<input_width$}  -> {}",
+                format!("{:<6}", "OK").green().bold(),
+                input_path,
+                output_path.display()
+            ),
+            Err(err) => {
+                let indent = " ".repeat(7 + input_width + 2);
+                let message = err.to_string().replace('\n', &format!("\n{}", indent));
+                println!(
+                    "{} {:<input_width$}  {}",
+                    "FAILED".red().bold(),
+                    input_path,
+                    message
+                );
+            }
+        }
+    }
+    println!();
+
+    let summary = format!(
+        "Converted {} of {} files in {}ms.",
+        results.len() - failed,
+        results.len(),
+        (std::time::Instant::now() - start_time).as_millis()
+    );
+    if failed == 0 {
+        println!("{}{} {}", "OK".green().bold(), ":".bold(), summary);
+        ExitCode::SUCCESS
+    } else {
+        eprintln!("{}{} {}", "Error".red().bold(), ":".bold(), summary);
+        ExitCode::FAILURE
+    }
+}
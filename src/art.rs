@@ -0,0 +1,372 @@
+use std::io::Read;
+
+use courageous_format::{
+    Alarm, Classification, Detection, DetectionRecord, Location, Position2d, Position3d, Track,
+    TrackingRecord,
+};
+use csv::StringRecord;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{Database, Error};
+
+const FORMAT: &str = "ART log";
+
+/// Converts the tracking, detection and identification logs of an ART system into a single
+/// [`Database`].
+///
+/// The format of these logs is not published, and no real log was available when this reader was
+/// written, so it assumes the structures below: CSV files with a header, whose kind is detected by
+/// their columns, and RFC 3339 times. `testdata/art_tracking.csv`, `testdata/art_detection.csv` and
+/// `testdata/art_identification.csv` are samples of them.
+///
+/// Tracking logs give the position of tracked targets. `Altitude` (AMSL) and `Classification` may
+/// be empty:
+/// ```text
+/// Time,TrackId,Latitude,Longitude,Altitude,Classification
+/// 2023-05-01T10:00:00.250Z,7,51.4507167,4.3341194,32.5,drone
+/// ```
+///
+/// Detection logs give the direction of targets that are not tracked. `Elevation` may be empty:
+/// ```text
+/// Time,Azimuth,Elevation
+/// 2023-05-01T10:00:00.500Z,45.5,2.25
+/// ```
+///
+/// Identification logs give the identity of tracked targets from some time on:
+/// ```text
+/// Time,TrackId,Identification
+/// 2023-05-01T10:00:01Z,7,DJI Mavic 3
+/// ```
+///
+/// Rows of tracking logs become `Position3d` records (`Position2d` without an altitude) of a
+/// [`Track`] with the track ID as UAS ID, in the order tracks first appear. Rows of detection logs
+/// become `BearingElevation` records (`Bearing` without an elevation) of a single [`Detection`]
+/// set. Records are numbered in the order they appear, reading logs in the order they are given.
+/// Classes are mapped ignoring case: `uav` and `drone` to [`Classification::Uav`], `gcs` and
+/// `unknown` to their equivalent classification, and every other class to
+/// [`Classification::Other`]. Records without one are classified as [`Classification::Unknown`].
+///
+/// Each identification is set on the records of its track from its time on, until the next
+/// identification of the same track. Identifications of tracks that do not appear in any tracking
+/// log are ignored.
+///
+/// ART logs do not store the location of the system, so it must be given as
+/// `static_cuas_location`. Detections are best written with
+/// [`WriteAsKmlOptions::angle_points`](crate::WriteAsKmlOptions::angle_points).
+pub fn read_art_logs<R: Read>(
+    logs: impl IntoIterator<Item = R>,
+    static_cuas_location: Position3d,
+) -> Result<Database, Error> {
+    let mut tracks: Vec<Track> = Vec::new();
+    let mut detection = Detection {
+        uas_id: None,
+        name: Some("ART detections".to_owned()),
+        records: Vec::new(),
+        uav_home_location: None,
+    };
+    // (Track ID, time, identification)
+    let mut identifications: Vec<(u64, u64, String)> = Vec::new();
+
+    let mut record_number = 0;
+    for log in logs {
+        let mut csv = csv::Reader::from_reader(log);
+        let headers = csv.headers()?.clone();
+        let column = |name: &str| headers.iter().position(|header| header.trim() == name);
+        let required =
+            |name: &str| column(name).ok_or_else(|| invalid(format!("missing {} column", name)));
+        let kind = if column("Latitude").is_some() {
+            LogKind::Tracking {
+                track: required("TrackId")?,
+                lat: required("Latitude")?,
+                lon: required("Longitude")?,
+                height: column("Altitude"),
+                classification: column("Classification"),
+            }
+        } else if column("Azimuth").is_some() {
+            LogKind::Detection {
+                bearing: required("Azimuth")?,
+                elevation: column("Elevation"),
+            }
+        } else if column("Identification").is_some() {
+            LogKind::Identification {
+                track: required("TrackId")?,
+                identification: required("Identification")?,
+            }
+        } else {
+            return Err(invalid(
+                "expected the columns of a tracking, detection or identification log",
+            ));
+        };
+        let time_column = required("Time")?;
+
+        for row in csv.records() {
+            let row = row?;
+            let time = parse_time(field(&row, time_column).unwrap_or(""))?;
+            match kind {
+                LogKind::Tracking {
+                    track,
+                    lat,
+                    lon,
+                    height,
+                    classification,
+                } => {
+                    let uas_id = parse_track_id(&row, track)?;
+                    let (Some(lat), Some(lon)) = (number(&row, lat)?, number(&row, lon)?) else {
+                        return Err(invalid(format!(
+                            "missing position in row for track {}",
+                            uas_id
+                        )));
+                    };
+                    let location = match height.map(|height| number(&row, height)).transpose()? {
+                        Some(Some(height)) => Location::Position3d(Position3d { lat, lon, height }),
+                        _ => Location::Position2d(Position2d { lat, lon }),
+                    };
+                    record_number += 1;
+                    let record = TrackingRecord {
+                        time,
+                        record_number,
+                        classification: parse_classification(
+                            classification.and_then(|column| field(&row, column)),
+                        ),
+                        alarm: Alarm {
+                            active: false,
+                            certainty: 0.,
+                        },
+                        location,
+                        identification: None,
+                        velocity: None,
+                        cuas_location: None,
+                    };
+                    match tracks.iter_mut().find(|track| track.uas_id == uas_id) {
+                        Some(track) => track.records.push(record),
+                        None => tracks.push(Track {
+                            uas_id,
+                            name: None,
+                            records: vec![record],
+                            uav_home_location: None,
+                        }),
+                    }
+                }
+                LogKind::Detection { bearing, elevation } => {
+                    let bearing = number(&row, bearing)?
+                        .ok_or_else(|| invalid("missing azimuth in detection row"))?;
+                    let location = match elevation.map(|column| number(&row, column)).transpose()? {
+                        Some(Some(elevation)) => Location::BearingElevation { bearing, elevation },
+                        _ => Location::Bearing { bearing },
+                    };
+                    record_number += 1;
+                    detection.records.push(DetectionRecord {
+                        time,
+                        record_number,
+                        classification: Classification::Unknown,
+                        alarm: None,
+                        location: Some(location),
+                        identification: None,
+                        velocity: None,
+                        cuas_location: None,
+                    });
+                }
+                LogKind::Identification {
+                    track,
+                    identification,
+                } => {
+                    let uas_id = parse_track_id(&row, track)?;
+                    if let Some(identification) = field(&row, identification) {
+                        identifications.push((uas_id, time, identification.to_owned()));
+                    }
+                }
+            }
+        }
+    }
+
+    // Later identifications take precedence
+    identifications.sort_by_key(|&(_, time, _)| time);
+    for track in &mut tracks {
+        for record in &mut track.records {
+            record.identification = identifications
+                .iter()
+                .rev()
+                .find(|(uas_id, time, _)| *uas_id == track.uas_id && *time <= record.time)
+                .map(|(_, _, identification)| identification.clone());
+        }
+    }
+
+    Ok(Database {
+        detection: if detection.records.is_empty() {
+            Vec::new()
+        } else {
+            vec![detection]
+        },
+        tracks,
+        static_cuas_location,
+    })
+}
+
+/// Indices of the columns of a log, depending on its kind.
+#[derive(Clone, Copy)]
+enum LogKind {
+    Tracking {
+        track: usize,
+        lat: usize,
+        lon: usize,
+        height: Option<usize>,
+        classification: Option<usize>,
+    },
+    Detection {
+        bearing: usize,
+        elevation: Option<usize>,
+    },
+    Identification {
+        track: usize,
+        identification: usize,
+    },
+}
+
+/// Returns the value of a column, if it is not empty.
+fn field(row: &StringRecord, column: usize) -> Option<&str> {
+    row.get(column)
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+fn number(row: &StringRecord, column: usize) -> Result<Option<f64>, Error> {
+    field(row, column)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| invalid(format!("invalid number {:?}", value)))
+        })
+        .transpose()
+}
+
+fn parse_track_id(row: &StringRecord, column: usize) -> Result<u64, Error> {
+    let value = field(row, column).unwrap_or("");
+    value
+        .parse()
+        .map_err(|_| invalid(format!("invalid track ID {:?}", value)))
+}
+
+fn parse_time(value: &str) -> Result<u64, Error> {
+    let time = OffsetDateTime::parse(value, &Rfc3339)
+        .map_err(|err| invalid(format!("invalid time {:?}: {}", value, err)))?;
+    u64::try_from(time.unix_timestamp_nanos() / 1_000_000)
+        .map_err(|_| invalid(format!("time {} is before the UNIX epoch", time)))
+}
+
+fn parse_classification(class: Option<&str>) -> Classification {
+    match class.map(str::to_lowercase).as_deref() {
+        Some("uav" | "drone") => Classification::Uav,
+        Some("gcs") => Classification::Gcs,
+        None | Some("unknown") => Classification::Unknown,
+        Some(_) => Classification::Other,
+    }
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidInput {
+        format: FORMAT,
+        message: message.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATIC_CUAS_LOCATION: Position3d = Position3d {
+        lat: 51.45,
+        lon: 4.33,
+        height: 15.,
+    };
+
+    #[test]
+    fn sample() {
+        let logs = [
+            include_str!("../testdata/art_tracking.csv"),
+            include_str!("../testdata/art_detection.csv"),
+            include_str!("../testdata/art_identification.csv"),
+        ];
+        let database = read_art_logs(logs.map(str::as_bytes), STATIC_CUAS_LOCATION).unwrap();
+
+        assert_eq!(database.tracks.len(), 2);
+        let track = &database.tracks[0];
+        assert_eq!(track.uas_id, 7);
+        assert_eq!(
+            track
+                .records
+                .iter()
+                .map(|record| (
+                    record.record_number,
+                    record.classification,
+                    record.identification.as_deref()
+                ))
+                .collect::<Vec<_>>(),
+            [
+                (1, Classification::Uav, None),
+                (3, Classification::Uav, Some("DJI Mavic 3")),
+                (4, Classification::Uav, Some("DJI Mavic 3 Pro")),
+            ]
+        );
+        let Location::Position3d(pos) = track.records[0].location else {
+            panic!("rows with an altitude must be read as Position3d");
+        };
+        assert_eq!(
+            (pos.lat, pos.lon, pos.height),
+            (51.4507167, 4.3341194, 32.5)
+        );
+        let record = &database.tracks[1].records[0];
+        assert_eq!(record.classification, Classification::Other);
+        assert!(matches!(record.location, Location::Position2d(_)));
+
+        assert_eq!(database.detection.len(), 1);
+        let records = &database.detection[0].records;
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| record.record_number)
+                .collect::<Vec<_>>(),
+            [5, 6]
+        );
+        let Some(Location::BearingElevation { bearing, elevation }) = records[0].location else {
+            panic!("detections with an elevation must be read as BearingElevation");
+        };
+        assert_eq!((bearing, elevation), (45.5, 2.25));
+        let Some(Location::Bearing { bearing }) = records[1].location else {
+            panic!("detections without an elevation must be read as Bearing");
+        };
+        assert_eq!(bearing, 310.);
+    }
+
+    #[test]
+    fn angle_points() {
+        let log = include_str!("../testdata/art_detection.csv");
+        let kml = |angle_points| {
+            let database = read_art_logs([log.as_bytes()], STATIC_CUAS_LOCATION).unwrap();
+            let mut kml = Vec::new();
+            crate::write_as_kml(
+                database,
+                &mut kml,
+                crate::WriteAsKmlOptions::default()
+                    .cuas_range(100.)
+                    .angle_points(angle_points),
+            )
+            .unwrap();
+            String::from_utf8(kml).unwrap()
+        };
+
+        assert_eq!(kml(false).matches("<LineString>").count(), 2);
+        let kml = kml(true);
+        assert!(!kml.contains("<LineString>"));
+        // Both detections and the CUAS origin
+        assert_eq!(kml.matches("<Point>").count(), 3);
+    }
+
+    #[test]
+    fn unknown_log() {
+        let log = "Time,Speed\n2023-05-01T10:00:00Z,3\n";
+        assert!(matches!(
+            read_art_logs([log.as_bytes()], STATIC_CUAS_LOCATION),
+            Err(Error::InvalidInput { .. })
+        ));
+    }
+}
@@ -0,0 +1,91 @@
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+
+use flate2::bufread::MultiGzDecoder;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression formats detected by [`decompress`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+/// A reader preceded by the bytes already read from it.
+type Peeked<R> = io::Chain<Cursor<Vec<u8>>, R>;
+
+/// Returns the compression format of the data read by `reader`, if any, by looking at its first
+/// bytes.
+///
+/// `reader` is read until enough bytes are available to tell, or until its end, since pipes may
+/// return fewer bytes than that at once. The returned reader reads the same data as `reader` would
+/// have, including the bytes that were looked at.
+pub fn detect_compression<R: BufRead>(
+    mut reader: R,
+) -> io::Result<(Option<Compression>, Peeked<R>)> {
+    let mut start = Vec::with_capacity(ZSTD_MAGIC.len());
+    while start.len() < ZSTD_MAGIC.len() {
+        let buf = match reader.fill_buf() {
+            Ok(buf) => buf,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        if buf.is_empty() {
+            break;
+        }
+        let len = buf.len().min(ZSTD_MAGIC.len() - start.len());
+        start.extend_from_slice(&buf[..len]);
+        reader.consume(len);
+    }
+
+    let compression = if start.starts_with(GZIP_MAGIC) {
+        Some(Compression::Gzip)
+    } else if start.starts_with(ZSTD_MAGIC) {
+        Some(Compression::Zstd)
+    } else {
+        None
+    };
+    Ok((compression, Cursor::new(start).chain(reader)))
+}
+
+/// Wraps `reader` in a decoder if its data is compressed with gzip or zstd, which are detected by
+/// their magic bytes. Uncompressed data is returned as is.
+///
+/// Data is decompressed as it is read, without storing it anywhere.
+pub fn decompress<'a>(reader: impl BufRead + 'a) -> io::Result<Box<dyn BufRead + 'a>> {
+    let (compression, reader) = detect_compression(reader)?;
+    Ok(match compression {
+        Some(Compression::Gzip) => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Some(Compression::Zstd) => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        None => Box::new(reader),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_reads() {
+        let data = [ZSTD_MAGIC, b"data"].concat();
+        // Returns a single byte at a time, like a slow pipe
+        let reader = BufReader::with_capacity(1, &data[..]);
+
+        let (compression, mut reader) = detect_compression(reader).unwrap();
+        assert_eq!(compression, Some(Compression::Zstd));
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, data);
+    }
+
+    #[test]
+    fn short_input() {
+        let (compression, mut reader) = detect_compression(&b"{}"[..]).unwrap();
+        assert_eq!(compression, None);
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "{}");
+    }
+}
@@ -0,0 +1,228 @@
+use std::{io::Write, time::Duration};
+
+use courageous_format::{Classification, Location, Position3d, Velocity};
+use quick_xml::{
+    events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event},
+    Writer,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    record::{check_record_position, classification_str, record_time},
+    Database, Error, RecordSet,
+};
+
+/// Value used by CoT for unknown heights and errors.
+const COT_UNKNOWN: f64 = 9999999.;
+
+#[derive(Clone)]
+#[non_exhaustive]
+pub struct WriteAsCotOptions {
+    /// How long each event is considered valid for after its time. Defaults to 10 seconds.
+    pub stale_after: Duration,
+}
+
+impl Default for WriteAsCotOptions {
+    fn default() -> Self {
+        Self {
+            stale_after: Duration::from_secs(10),
+        }
+    }
+}
+
+impl WriteAsCotOptions {
+    pub fn stale_after(mut self, val: Duration) -> WriteAsCotOptions {
+        self.stale_after = val;
+        self
+    }
+}
+
+/// A Cursor-on-Target event, representing a single positional tracking record.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CotEvent {
+    /// Shared by all events of the same track, so that TAK clients update a single contact.
+    pub uid: String,
+    /// Index of the track the event belongs to, inside [`Database::tracks`].
+    pub track: usize,
+    pub record_number: u64,
+    /// CoT type, derived from the classification of the record.
+    pub event_type: &'static str,
+    pub callsign: String,
+    pub time: OffsetDateTime,
+    pub stale: OffsetDateTime,
+    pub lat: f64,
+    pub lon: f64,
+    /// Height above the WGS84 ellipsoid, if known.
+    ///
+    /// COURAGEOUS heights are AMSL, which differ from it by the geoid undulation (tens of meters).
+    /// Since converting them would require a geoid model, [`cot_events`] leaves this unknown and
+    /// writes the AMSL height into the remarks instead. It can be set if the conversion is known.
+    pub hae: Option<f64>,
+    /// Course (in degrees from north) and speed (in m/s), if the velocity of the record is known.
+    pub course_speed: Option<(f64, f64)>,
+    pub remarks: String,
+}
+
+/// Writes `database` as an `<events>` element containing one CoT event per tracking record with
+/// an absolute position, loadable on ATAK/WinTAK.
+pub fn write_as_cot(
+    database: Database,
+    writer: impl Write,
+    options: WriteAsCotOptions,
+) -> Result<(), Error> {
+    let mut xml = Writer::new(writer);
+    write_declaration(&mut xml)?;
+    xml.write_event(Event::Start(BytesStart::new("events")))?;
+    for event in cot_events(&database, &options)? {
+        write_event(&mut xml, &event)?;
+    }
+    xml.write_event(Event::End(BytesEnd::new("events")))?;
+    Ok(())
+}
+
+/// Creates one CoT event per tracking record with an absolute position in `database`.
+///
+/// Records with other locations (e.g. bearings) cannot be represented in CoT and are skipped.
+pub fn cot_events(
+    database: &Database,
+    options: &WriteAsCotOptions,
+) -> Result<Vec<CotEvent>, Error> {
+    let mut events = Vec::new();
+    for (idx, track) in database.tracks.iter().enumerate() {
+        let set = RecordSet::Track(idx);
+        let uid = format!("track2kml.uas-{}", track.uas_id);
+        let callsign = track
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("UAS {}", track.uas_id));
+        for record in &track.records {
+            let (pos, height_amsl) = match record.location {
+                Location::Position3d(pos) => (pos, Some(pos.height)),
+                Location::Position2d(pos) => (
+                    Position3d {
+                        lat: pos.lat,
+                        lon: pos.lon,
+                        height: 0.,
+                    },
+                    None,
+                ),
+                _ => continue,
+            };
+            let pos = check_record_position(pos, set, record.record_number)?;
+            let time = record_time(record.time, set, record.record_number)?;
+            let stale = time::Duration::try_from(options.stale_after)
+                .ok()
+                .and_then(|stale_after| time.checked_add(stale_after))
+                .ok_or(Error::InvalidStaleTime {
+                    set,
+                    record_number: record.record_number,
+                })?;
+            let mut remarks = format!(
+                "Classification: {}",
+                classification_str(record.classification)
+            );
+            if let Some(height) = height_amsl {
+                remarks += &format!(", Height: {} m AMSL", height);
+            }
+            if let Some(identification) = &record.identification {
+                remarks += &format!(", Identification: {}", identification);
+            }
+            if record.alarm.active {
+                remarks += &format!(", Alarm ({:.0}% certainty)", record.alarm.certainty * 100.);
+            }
+
+            events.push(CotEvent {
+                uid: uid.clone(),
+                track: idx,
+                record_number: record.record_number,
+                event_type: event_type(record.classification),
+                callsign: callsign.clone(),
+                time,
+                stale,
+                lat: pos.lat,
+                lon: pos.lon,
+                hae: None,
+                course_speed: record.velocity.map(course_speed),
+                remarks,
+            });
+        }
+    }
+    Ok(events)
+}
+
+/// Writes a single CoT event into `writer`, as a standalone XML document.
+pub fn write_cot_event(event: &CotEvent, writer: impl Write) -> Result<(), Error> {
+    let mut xml = Writer::new(writer);
+    write_declaration(&mut xml)?;
+    write_event(&mut xml, event)
+}
+
+fn write_declaration(x: &mut Writer<impl Write>) -> Result<(), Error> {
+    x.write_event(Event::Decl(BytesDecl::new(
+        "1.0",
+        Some("UTF-8"),
+        Some("yes"),
+    )))?;
+    Ok(())
+}
+
+fn write_event(x: &mut Writer<impl Write>, event: &CotEvent) -> Result<(), Error> {
+    let time = event.time.format(&Rfc3339)?;
+    let stale = event.stale.format(&Rfc3339)?;
+    x.create_element("event")
+        .with_attribute(("version", "2.0"))
+        .with_attribute(("uid", event.uid.as_str()))
+        .with_attribute(("type", event.event_type))
+        .with_attribute(("how", "m-g"))
+        .with_attribute(("time", time.as_str()))
+        .with_attribute(("start", time.as_str()))
+        .with_attribute(("stale", stale.as_str()))
+        .write_inner_content(|x| {
+            x.create_element("point")
+                .with_attribute(("lat", event.lat.to_string().as_str()))
+                .with_attribute(("lon", event.lon.to_string().as_str()))
+                .with_attribute(("hae", event.hae.unwrap_or(COT_UNKNOWN).to_string().as_str()))
+                .with_attribute(("ce", COT_UNKNOWN.to_string().as_str()))
+                .with_attribute(("le", COT_UNKNOWN.to_string().as_str()))
+                .write_empty()?;
+            x.create_element("detail").write_inner_content(|x| {
+                x.create_element("contact")
+                    .with_attribute(("callsign", event.callsign.as_str()))
+                    .write_empty()?;
+                if let Some((course, speed)) = event.course_speed {
+                    x.create_element("track")
+                        .with_attribute(("course", course.to_string().as_str()))
+                        .with_attribute(("speed", speed.to_string().as_str()))
+                        .write_empty()?;
+                }
+                x.create_element("remarks")
+                    .write_text_content(BytesText::new(&event.remarks))?;
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+    Ok(())
+}
+
+/// Returns the CoT type (as defined by MIL-STD-2525) of a record with the given classification.
+/// Affiliation is always unknown.
+fn event_type(classification: Classification) -> &'static str {
+    match classification {
+        // Air track, military, fixed wing, drone (UAV)
+        Classification::Uav => "a-u-A-M-F-Q",
+        // Ground unit
+        Classification::Gcs => "a-u-G-U",
+        Classification::Other => "a-u-A",
+        Classification::Unknown => "a-u",
+    }
+}
+
+fn course_speed(velocity: Velocity) -> (f64, f64) {
+    let course = velocity
+        .east
+        .atan2(velocity.north)
+        .to_degrees()
+        .rem_euclid(360.);
+    let speed = velocity.east.hypot(velocity.north);
+    (course, speed)
+}
@@ -0,0 +1,79 @@
+use std::io::Write;
+
+use courageous_format::{Location, Position3d};
+
+use crate::{
+    record::{check_cuas_location, check_record_position, record_time},
+    Database, Error, RecordSet,
+};
+
+/// Writes `database` as a pretty-printed COURAGEOUS JSON document.
+///
+/// The database is validated before anything is written, with the same checks the other exporters
+/// apply: Timestamps must be representable as RFC 3339 dates, and every absolute position and CUAS
+/// location must have a valid latitude and longitude and a finite height.
+pub fn write_as_courageous(database: Database, mut writer: impl Write) -> Result<(), Error> {
+    check_database(&database)?;
+    serde_json::to_writer_pretty(&mut writer, &database)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+fn check_database(database: &Database) -> Result<(), Error> {
+    check_cuas_location(database.static_cuas_location)?;
+    for (idx, detection) in database.detection.iter().enumerate() {
+        for record in &detection.records {
+            check_record(
+                RecordSet::Detection(idx),
+                record.record_number,
+                record.time,
+                record.location,
+                record.cuas_location,
+            )?;
+        }
+    }
+    for (idx, track) in database.tracks.iter().enumerate() {
+        for record in &track.records {
+            check_record(
+                RecordSet::Track(idx),
+                record.record_number,
+                record.time,
+                Some(record.location),
+                record.cuas_location,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks the time, absolute position and CUAS location of a record.
+pub(crate) fn check_record(
+    set: RecordSet,
+    record_number: u64,
+    time: u64,
+    location: Option<Location>,
+    cuas_location: Option<Position3d>,
+) -> Result<(), Error> {
+    record_time(time, set, record_number)?;
+    match location {
+        Some(Location::Position3d(pos)) => {
+            check_record_position(pos, set, record_number)?;
+        }
+        Some(Location::Position2d(pos)) => {
+            check_record_position(
+                Position3d {
+                    lat: pos.lat,
+                    lon: pos.lon,
+                    height: 0.,
+                },
+                set,
+                record_number,
+            )?;
+        }
+        _ => {}
+    }
+    if let Some(cuas_location) = cuas_location {
+        check_cuas_location(cuas_location)?;
+    }
+    Ok(())
+}
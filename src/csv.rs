@@ -0,0 +1,227 @@
+use std::io::Write;
+
+use courageous_format::{Alarm, Arc, Classification, Location, Position3d, Quad, Velocity};
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+
+use crate::{
+    kml::geometry::distance_from_position,
+    record::{check_cuas_location, classification_str, record_time},
+    Database, Error, RecordSet,
+};
+
+#[derive(Clone, Default)]
+#[non_exhaustive]
+pub struct WriteAsCsvOptions {
+    /// Overrides the `static_cuas_location` member of the database if given.
+    pub static_cuas_location: Option<Position3d>,
+}
+
+impl WriteAsCsvOptions {
+    pub fn static_cuas_location(mut self, val: Option<Position3d>) -> WriteAsCsvOptions {
+        self.static_cuas_location = val;
+        self
+    }
+}
+
+/// Writes every record of `database` as a row of a CSV table, with a header row.
+///
+/// Besides the members of the record, each row contains the raw fields of its location (Empty if
+/// they do not apply) and the absolute position it resolves to, if it can be calculated: Only
+/// `Position2d`, `Position3d` and `BearingElevationDistance` locations have one.
+pub fn write_as_csv(
+    database: Database,
+    writer: impl Write,
+    options: WriteAsCsvOptions,
+) -> Result<(), Error> {
+    let static_cuas_location = options
+        .static_cuas_location
+        .unwrap_or(database.static_cuas_location);
+    check_cuas_location(static_cuas_location)?;
+
+    let mut csv = csv::Writer::from_writer(writer);
+    for (idx, detection) in database.detection.iter().enumerate() {
+        for record in &detection.records {
+            csv.serialize(Row::new(
+                RecordSet::Detection(idx),
+                detection.uas_id,
+                detection.name.as_deref(),
+                RecordData {
+                    time: record.time,
+                    record_number: record.record_number,
+                    classification: record.classification,
+                    alarm: record.alarm,
+                    identification: record.identification.as_deref(),
+                    velocity: record.velocity,
+                    location: record.location,
+                    cuas_location: record.cuas_location.unwrap_or(static_cuas_location),
+                },
+            )?)?;
+        }
+    }
+    for (idx, track) in database.tracks.iter().enumerate() {
+        for record in &track.records {
+            csv.serialize(Row::new(
+                RecordSet::Track(idx),
+                Some(track.uas_id),
+                track.name.as_deref(),
+                RecordData {
+                    time: record.time,
+                    record_number: record.record_number,
+                    classification: record.classification,
+                    alarm: Some(record.alarm),
+                    identification: record.identification.as_deref(),
+                    velocity: record.velocity,
+                    location: Some(record.location),
+                    cuas_location: record.cuas_location.unwrap_or(static_cuas_location),
+                },
+            )?)?;
+        }
+    }
+    csv.flush()?;
+
+    Ok(())
+}
+
+/// The members of tracking and detection records written into each row.
+struct RecordData<'a> {
+    time: u64,
+    record_number: u64,
+    classification: Classification,
+    alarm: Option<Alarm>,
+    identification: Option<&'a str>,
+    velocity: Option<Velocity>,
+    location: Option<Location>,
+    /// The CUAS location the record is relative to.
+    cuas_location: Position3d,
+}
+
+#[derive(Default, Serialize)]
+struct Row<'a> {
+    /// `track` or `detection`.
+    source: &'static str,
+    /// Index of the track or detection set inside the document.
+    set: usize,
+    uas_id: Option<u64>,
+    name: Option<&'a str>,
+    record_number: u64,
+    time: String,
+    classification: &'static str,
+    alarm: Option<bool>,
+    alarm_certainty: Option<f64>,
+    identification: Option<&'a str>,
+    velocity_east: Option<f64>,
+    velocity_north: Option<f64>,
+    velocity_up: Option<f64>,
+    location: Option<&'static str>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    height: Option<f64>,
+    bearing: Option<f64>,
+    elevation: Option<f64>,
+    distance: Option<f64>,
+    quad: Option<&'static str>,
+    arc_from: Option<f64>,
+    arc_to: Option<f64>,
+    resolved_lat: Option<f64>,
+    resolved_lon: Option<f64>,
+    resolved_height: Option<f64>,
+}
+
+impl<'a> Row<'a> {
+    fn new(
+        set: RecordSet,
+        uas_id: Option<u64>,
+        name: Option<&'a str>,
+        record: RecordData<'a>,
+    ) -> Result<Row<'a>, Error> {
+        let (source, set_idx) = match set {
+            RecordSet::Track(idx) => ("track", idx),
+            RecordSet::Detection(idx) => ("detection", idx),
+        };
+        let mut row = Row {
+            source,
+            set: set_idx,
+            uas_id,
+            name,
+            record_number: record.record_number,
+            time: record_time(record.time, set, record.record_number)?.format(&Rfc3339)?,
+            classification: classification_str(record.classification),
+            alarm: record.alarm.map(|a| a.active),
+            alarm_certainty: record.alarm.map(|a| a.certainty),
+            identification: record.identification,
+            velocity_east: record.velocity.map(|v| v.east),
+            velocity_north: record.velocity.map(|v| v.north),
+            velocity_up: record.velocity.map(|v| v.up),
+            ..Default::default()
+        };
+
+        let resolved = match record.location {
+            Some(Location::Position3d(pos)) => {
+                row.location = Some("Position3d");
+                (row.lat, row.lon, row.height) = (Some(pos.lat), Some(pos.lon), Some(pos.height));
+                Some(pos)
+            }
+            Some(Location::Position2d(pos)) => {
+                row.location = Some("Position2d");
+                (row.lat, row.lon) = (Some(pos.lat), Some(pos.lon));
+                Some(Position3d {
+                    lat: pos.lat,
+                    lon: pos.lon,
+                    height: 0.,
+                })
+            }
+            Some(Location::BearingElevationDistance {
+                bearing,
+                elevation,
+                distance,
+            }) => {
+                row.location = Some("BearingElevationDistance");
+                (row.bearing, row.elevation, row.distance) =
+                    (Some(bearing), Some(elevation), Some(distance));
+                Some(distance_from_position(
+                    record.cuas_location,
+                    bearing,
+                    elevation,
+                    distance,
+                ))
+            }
+            Some(Location::BearingElevation { bearing, elevation }) => {
+                row.location = Some("BearingElevation");
+                (row.bearing, row.elevation) = (Some(bearing), Some(elevation));
+                None
+            }
+            Some(Location::Bearing { bearing }) => {
+                row.location = Some("Bearing");
+                row.bearing = Some(bearing);
+                None
+            }
+            Some(Location::Quad { quad }) => {
+                row.location = Some("Quad");
+                row.quad = Some(quad_str(quad));
+                None
+            }
+            Some(Location::Arc(Arc { from, to })) => {
+                row.location = Some("Arc");
+                (row.arc_from, row.arc_to) = (Some(from), Some(to));
+                None
+            }
+            None => None,
+        };
+        if let Some(pos) = resolved {
+            (row.resolved_lat, row.resolved_lon, row.resolved_height) =
+                (Some(pos.lat), Some(pos.lon), Some(pos.height));
+        }
+
+        Ok(row)
+    }
+}
+
+fn quad_str(quad: Quad) -> &'static str {
+    match quad {
+        Quad::North => "North",
+        Quad::East => "East",
+        Quad::South => "South",
+        Quad::West => "West",
+    }
+}
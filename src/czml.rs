@@ -0,0 +1,398 @@
+use std::io::Write;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use courageous_format::{Arc, Classification, Location, Position3d};
+use serde_json::{json, Value};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+
+use crate::{
+    kml::geometry::{arc_sector, distance_from_position, quad_bearings},
+    record::{check_cuas_location, check_record_position, classification_str, record_time},
+    Database, Error, RecordSet,
+};
+
+/// How long the last record of a track or detection set is shown for, and the minimum for every
+/// other record.
+const LAST_RECORD_DURATION: Duration = Duration::seconds(1);
+
+#[derive(Clone, Default)]
+#[non_exhaustive]
+pub struct WriteAsCzmlOptions {
+    pub cuas_range: f64,
+    /// Overrides the `static_cuas_location` member of the database if given.
+    pub static_cuas_location: Option<Position3d>,
+}
+
+impl WriteAsCzmlOptions {
+    pub fn cuas_range(mut self, val: f64) -> WriteAsCzmlOptions {
+        self.cuas_range = val;
+        self
+    }
+    pub fn static_cuas_location(mut self, val: Option<Position3d>) -> WriteAsCzmlOptions {
+        self.static_cuas_location = val;
+        self
+    }
+}
+
+/// Writes `database` as a CZML document, for time-dynamic replay on Cesium.
+///
+/// Positional records of each track are written as a single time-sampled entity per
+/// classification. Every other record (including all detection records) is written as an entity
+/// available from its time until the time of the next record of the same track or detection set,
+/// and at least for one second. Records are sorted by time first.
+pub fn write_as_czml(
+    database: Database,
+    writer: impl Write,
+    options: WriteAsCzmlOptions,
+) -> Result<(), Error> {
+    let static_cuas_location = options
+        .static_cuas_location
+        .unwrap_or(database.static_cuas_location);
+    check_cuas_location(static_cuas_location)?;
+    let ctx = Context {
+        static_cuas_location,
+        cuas_range: options.cuas_range,
+    };
+
+    let mut packets = Vec::new();
+    let mut interval: Option<(OffsetDateTime, OffsetDateTime)> = None;
+    let mut extend_interval = |records: &[TimedLocation], set: RecordSet| -> Result<(), Error> {
+        for record in records {
+            let end = record_end(record, set)?;
+            interval = Some(match interval {
+                Some((start, interval_end)) => (start.min(record.time), interval_end.max(end)),
+                None => (record.time, end),
+            });
+        }
+        Ok(())
+    };
+
+    for (idx, detection) in database.detection.iter().enumerate() {
+        let set = RecordSet::Detection(idx);
+        let mut records = detection
+            .records
+            .iter()
+            .filter_map(|record| record.location.map(|location| (record, location)))
+            .map(|(record, location)| {
+                Ok(TimedLocation {
+                    time: record_time(record.time, set, record.record_number)?,
+                    timestamp: record.time,
+                    record_number: record.record_number,
+                    location,
+                    cuas_location: record.cuas_location,
+                    classification: record.classification,
+                    identification: record.identification.as_deref(),
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        records.sort_by_key(|r| r.time);
+        extend_interval(&records, set)?;
+        record_packets(
+            &mut packets,
+            &format!("detection/{}", idx),
+            set,
+            &records,
+            &ctx,
+        )?;
+    }
+
+    for (idx, track) in database.tracks.iter().enumerate() {
+        let set = RecordSet::Track(idx);
+        let mut records = track
+            .records
+            .iter()
+            .map(|record| {
+                Ok(TimedLocation {
+                    time: record_time(record.time, set, record.record_number)?,
+                    timestamp: record.time,
+                    record_number: record.record_number,
+                    location: record.location,
+                    cuas_location: record.cuas_location,
+                    classification: record.classification,
+                    identification: record.identification.as_deref(),
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        records.sort_by_key(|r| r.time);
+        extend_interval(&records, set)?;
+
+        let name = track
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("Unnamed track (UAS ID {})", track.uas_id));
+        track_packets(&mut packets, idx, &name, &records)?;
+        let relative_records = records
+            .into_iter()
+            .filter(|r| {
+                !matches!(
+                    r.location,
+                    Location::Position2d(_) | Location::Position3d(_)
+                )
+            })
+            .collect::<Vec<_>>();
+        record_packets(
+            &mut packets,
+            &format!("track/{}", idx),
+            set,
+            &relative_records,
+            &ctx,
+        )?;
+    }
+
+    packets.push(json!({
+        "id": "cuas",
+        "name": "Static CUAS Location",
+        "position": { "cartographicDegrees": position(static_cuas_location) },
+        "billboard": {
+            "image": format!(
+                "data:image/png;base64,{}",
+                STANDARD.encode(include_bytes!("kml/icons/cuas.png"))
+            ),
+            "verticalOrigin": "BOTTOM",
+        },
+    }));
+
+    let mut document = json!({
+        "id": "document",
+        "name": "track2kml",
+        "version": "1.0",
+    });
+    if let Some((start, end)) = interval {
+        document["clock"] = json!({
+            "interval": interval_str(start, end)?,
+            "currentTime": start.format(&Rfc3339)?,
+            "multiplier": 1,
+            "range": "LOOP_STOP",
+            "step": "SYSTEM_CLOCK_MULTIPLIER",
+        });
+    }
+    packets.insert(0, document);
+
+    serde_json::to_writer(writer, &packets)?;
+    Ok(())
+}
+
+struct Context {
+    static_cuas_location: Position3d,
+    cuas_range: f64,
+}
+
+/// The members of tracking and detection records relevant to CZML output.
+struct TimedLocation<'a> {
+    time: OffsetDateTime,
+    /// `time` as given in the document, for reporting errors.
+    timestamp: u64,
+    record_number: u64,
+    location: Location,
+    cuas_location: Option<Position3d>,
+    classification: Classification,
+    identification: Option<&'a str>,
+}
+
+/// Returns when `record` stops being shown if no other record replaces it.
+fn record_end(record: &TimedLocation, set: RecordSet) -> Result<OffsetDateTime, Error> {
+    record
+        .time
+        .checked_add(LAST_RECORD_DURATION)
+        .ok_or(Error::InvalidTimestamp {
+            set,
+            record_number: record.record_number,
+            time: record.timestamp,
+        })
+}
+
+/// Creates one time-sampled entity per classification with the positional records of a track.
+/// `records` must be sorted by time.
+fn track_packets(
+    packets: &mut Vec<Value>,
+    idx: usize,
+    name: &str,
+    records: &[TimedLocation],
+) -> Result<(), Error> {
+    let set = RecordSet::Track(idx);
+    for classification in [
+        Classification::Uav,
+        Classification::Gcs,
+        Classification::Other,
+        Classification::Unknown,
+    ] {
+        let mut samples = Vec::new();
+        let mut clamp_to_ground = false;
+        let mut pos_records = records
+            .iter()
+            .filter(|r| r.classification == classification)
+            .filter_map(|r| match r.location {
+                Location::Position3d(pos) => Some((r, pos)),
+                Location::Position2d(pos) => {
+                    clamp_to_ground = true;
+                    Some((
+                        r,
+                        Position3d {
+                            lat: pos.lat,
+                            lon: pos.lon,
+                            height: 0.,
+                        },
+                    ))
+                }
+                _ => None,
+            })
+            .peekable();
+        let Some(&(first, _)) = pos_records.peek() else {
+            continue;
+        };
+        let epoch = first.time;
+        let mut last = first;
+        for (record, pos) in pos_records {
+            let pos = check_record_position(pos, set, record.record_number)?;
+            samples.push((record.time - epoch).as_seconds_f64());
+            samples.extend(position(pos));
+            last = record;
+        }
+
+        let color = classification_color(classification);
+        packets.push(json!({
+            "id": format!("track/{}/{}", idx, classification_str(classification)),
+            "name": format!("{} ({})", name, classification_str(classification)),
+            "availability": interval_str(epoch, record_end(last, set)?)?,
+            "position": {
+                "epoch": epoch.format(&Rfc3339)?,
+                "cartographicDegrees": samples,
+            },
+            "point": {
+                "pixelSize": 8,
+                "color": { "rgba": color },
+                "heightReference": if clamp_to_ground { "CLAMP_TO_GROUND" } else { "NONE" },
+            },
+            "path": {
+                "width": 3,
+                "leadTime": 0,
+                "resolution": 1,
+                "material": { "solidColor": { "color": { "rgba": color } } },
+            },
+            "properties": {
+                "classification": classification_str(classification),
+            },
+        }));
+    }
+    Ok(())
+}
+
+/// Creates one entity per record, each available until the time of the next record, and at least
+/// for [`LAST_RECORD_DURATION`]. `records` must be sorted by time.
+fn record_packets(
+    packets: &mut Vec<Value>,
+    id_prefix: &str,
+    set: RecordSet,
+    records: &[TimedLocation],
+    ctx: &Context,
+) -> Result<(), Error> {
+    for (idx, record) in records.iter().enumerate() {
+        let end = record_end(record, set)?;
+        let end = records.get(idx + 1).map_or(end, |next| end.max(next.time));
+        let cuas_origin = record.cuas_location.unwrap_or(ctx.static_cuas_location);
+        let color = classification_color(record.classification);
+        let point = |pos: Position3d, height_reference: &str| {
+            json!({
+                "position": { "cartographicDegrees": position(pos) },
+                "point": {
+                    "pixelSize": 8,
+                    "color": { "rgba": color },
+                    "heightReference": height_reference,
+                },
+            })
+        };
+        let ray = |elevation: f64, bearing: f64, clamp_to_ground: bool| {
+            let target = distance_from_position(cuas_origin, bearing, elevation, ctx.cuas_range);
+            json!({
+                "polyline": {
+                    "positions": {
+                        "cartographicDegrees": ([position(cuas_origin), position(target)].concat()),
+                    },
+                    "width": 2,
+                    "clampToGround": clamp_to_ground,
+                    "material": { "solidColor": { "color": { "rgba": color } } },
+                },
+            })
+        };
+        let sector = |bearing_from: f64, bearing_to: f64| {
+            let outline = arc_sector(bearing_from, bearing_to, cuas_origin, ctx.cuas_range);
+            json!({
+                "polygon": {
+                    "positions": {
+                        "cartographicDegrees": outline.into_iter().flat_map(position).collect::<Vec<_>>(),
+                    },
+                    "material": {
+                        "solidColor": { "color": { "rgba": [color[0], color[1], color[2], 96] } },
+                    },
+                    "outline": true,
+                    "outlineColor": { "rgba": color },
+                },
+            })
+        };
+
+        let mut packet = match record.location {
+            Location::Position3d(pos) => point(
+                check_record_position(pos, set, record.record_number)?,
+                "NONE",
+            ),
+            Location::Position2d(pos) => point(
+                check_record_position(
+                    Position3d {
+                        lat: pos.lat,
+                        lon: pos.lon,
+                        height: 0.,
+                    },
+                    set,
+                    record.record_number,
+                )?,
+                "CLAMP_TO_GROUND",
+            ),
+            Location::BearingElevationDistance {
+                bearing,
+                elevation,
+                distance,
+            } => point(
+                distance_from_position(cuas_origin, bearing, elevation, distance),
+                "NONE",
+            ),
+            Location::BearingElevation { bearing, elevation } => ray(elevation, bearing, false),
+            Location::Bearing { bearing } => ray(0., bearing, true),
+            Location::Quad { quad } => {
+                let (bearing_from, bearing_to) = quad_bearings(quad);
+                sector(bearing_from, bearing_to)
+            }
+            Location::Arc(Arc { from, to }) => sector(from, to),
+        };
+        packet["id"] = json!(format!("{}/{}", id_prefix, idx));
+        packet["name"] = json!(format!("Record {}", record.record_number));
+        packet["availability"] = json!(interval_str(record.time, end)?);
+        packet["properties"] = json!({
+            "record_number": record.record_number,
+            "classification": classification_str(record.classification),
+            "identification": record.identification,
+        });
+        packets.push(packet);
+    }
+    Ok(())
+}
+
+fn classification_color(classification: Classification) -> [u8; 4] {
+    match classification {
+        Classification::Uav => [230, 40, 40, 255],
+        Classification::Gcs => [240, 200, 30, 255],
+        Classification::Other => [200, 60, 220, 255],
+        Classification::Unknown => [160, 160, 160, 255],
+    }
+}
+
+fn interval_str(start: OffsetDateTime, end: OffsetDateTime) -> Result<String, Error> {
+    Ok(format!(
+        "{}/{}",
+        start.format(&Rfc3339)?,
+        end.format(&Rfc3339)?
+    ))
+}
+
+fn position(pos: Position3d) -> [f64; 3] {
+    [pos.lon, pos.lat, pos.height]
+}
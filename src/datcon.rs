@@ -0,0 +1,258 @@
+use std::io::Read;
+
+use courageous_format::{
+    Alarm, Classification, Location, Position3d, Track, TrackingRecord, Velocity,
+};
+use csv::StringRecord;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::Error;
+
+const FORMAT: &str = "DATCON CSV";
+
+/// Converts a CSV file exported by DatCon from a DJI flight log into a [`Track`] of `Position3d`
+/// records, for use as ground truth.
+///
+/// Columns are found by their group and field names, ignoring the index DatCon adds to the group
+/// (e.g. `GPS(0):Lat` is found as `GPS:Lat`). The following ones are used:
+/// - `GPS:Lat`, `GPS:Long` and `GPS:heightMSL` for the position of each record.
+/// - `GPS:dateTimeStamp` for the time of each record. If the `offsetTime` column is present, it is
+///   used to give records sub-second precision.
+/// - `GPS:velN`, `GPS:velE` and `GPS:velD` for the velocity of each record, if present.
+/// - `HOME:Latitude`, `HOME:Longitude` and `HOME:height` for the UAV home location, if present.
+///
+/// DatCon writes a row for every sample of any sensor, so rows without a GPS fix or whose GPS data
+/// has not changed since the previous row are skipped.
+pub fn read_datcon_track(reader: impl Read, uas_id: u64) -> Result<Track, Error> {
+    let mut csv = csv::Reader::from_reader(reader);
+    let headers = csv.headers()?.clone();
+    let required = |group, field| {
+        find_column(&headers, group, field)
+            .ok_or_else(|| invalid(format!("missing {}:{} column", group, field)))
+    };
+    let lat_column = required("GPS", "Lat")?;
+    let lon_column = required("GPS", "Long")?;
+    let height_column = required("GPS", "heightMSL")?;
+    let time_column = required("GPS", "dateTimeStamp")?;
+    let offset_column = headers.iter().position(|header| header == "offsetTime");
+    let velocity_columns = (
+        find_column(&headers, "GPS", "velN"),
+        find_column(&headers, "GPS", "velE"),
+        find_column(&headers, "GPS", "velD"),
+    );
+    let home_columns = (
+        find_column(&headers, "HOME", "Latitude"),
+        find_column(&headers, "HOME", "Longitude"),
+        find_column(&headers, "HOME", "height"),
+    );
+
+    let mut samples: Vec<Sample> = Vec::new();
+    let mut uav_home_location = None;
+    for row in csv.records() {
+        let row = row?;
+        let value = |column: usize| -> Result<Option<f64>, Error> {
+            match row.get(column).map(str::trim) {
+                None | Some("") => Ok(None),
+                Some(value) => value
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| invalid(format!("invalid number {:?}", value))),
+            }
+        };
+
+        if let (None, (Some(lat), Some(lon), height)) = (uav_home_location, home_columns) {
+            if let (Some(lat), Some(lon)) = (value(lat)?, value(lon)?) {
+                if lat != 0. || lon != 0. {
+                    let height = height.map(value).transpose()?.flatten().unwrap_or(0.);
+                    uav_home_location = Some(Position3d { lat, lon, height });
+                }
+            }
+        }
+
+        let (Some(lat), Some(lon), Some(height)) = (
+            value(lat_column)?,
+            value(lon_column)?,
+            value(height_column)?,
+        ) else {
+            continue;
+        };
+        let time_stamp = match row.get(time_column).map(str::trim) {
+            None | Some("") => continue,
+            Some(time_stamp) => OffsetDateTime::parse(time_stamp, &Rfc3339)
+                .map_err(|err| invalid(format!("invalid time {:?}: {}", time_stamp, err)))?,
+        };
+        // Without a GPS fix, DatCon writes zeroed coordinates
+        if lat == 0. && lon == 0. {
+            continue;
+        }
+        let velocity = match velocity_columns {
+            (Some(north), Some(east), Some(down)) => {
+                match (value(north)?, value(east)?, value(down)?) {
+                    (Some(north), Some(east), Some(down)) => Some(Velocity {
+                        east,
+                        north,
+                        up: -down,
+                    }),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        let sample = Sample {
+            time_stamp: unix_millis(time_stamp)?,
+            offset: offset_column.map(value).transpose()?.flatten(),
+            position: Position3d { lat, lon, height },
+            velocity,
+        };
+        if samples.last().is_some_and(|last| last.same_fix(&sample)) {
+            continue;
+        }
+        samples.push(sample);
+    }
+
+    // `dateTimeStamp` only has a precision of one second. The moment it changes is used to
+    // find the time at which the log started, from which the time of every sample is calculated
+    // with `offsetTime` instead.
+    let log_start = samples.windows(2).find_map(|pair| {
+        let (previous, sample) = (&pair[0], &pair[1]);
+        (sample.time_stamp != previous.time_stamp)
+            .then_some(sample.offset)
+            .flatten()
+            .map(|offset| sample.time_stamp as f64 - offset * 1000.)
+    });
+
+    let records = samples
+        .into_iter()
+        .zip(1..)
+        .map(|(sample, record_number)| {
+            let time = match (log_start, sample.offset) {
+                (Some(log_start), Some(offset)) => (log_start + offset * 1000.).round() as u64,
+                _ => sample.time_stamp,
+            };
+            TrackingRecord {
+                time,
+                record_number,
+                classification: Classification::Uav,
+                alarm: Alarm {
+                    active: false,
+                    certainty: 0.,
+                },
+                location: Location::Position3d(sample.position),
+                identification: None,
+                velocity: sample.velocity,
+                cuas_location: None,
+            }
+        })
+        .collect();
+
+    Ok(Track {
+        uas_id,
+        name: Some("Ground truth (DATCON)".to_owned()),
+        records,
+        uav_home_location,
+    })
+}
+
+struct Sample {
+    /// Value of `GPS:dateTimeStamp`, in milliseconds since the UNIX epoch.
+    time_stamp: u64,
+    /// Value of `offsetTime`: Seconds since the start of the log.
+    offset: Option<f64>,
+    position: Position3d,
+    velocity: Option<Velocity>,
+}
+
+impl Sample {
+    /// Returns whether both samples contain the same GPS data.
+    fn same_fix(&self, other: &Sample) -> bool {
+        let velocity = |sample: &Sample| sample.velocity.map(|v| (v.east, v.north, v.up));
+        self.time_stamp == other.time_stamp
+            && (self.position.lat, self.position.lon, self.position.height)
+                == (
+                    other.position.lat,
+                    other.position.lon,
+                    other.position.height,
+                )
+            && velocity(self) == velocity(other)
+    }
+}
+
+/// Returns the index of the column named `<group>(<index>):<field>` or `<group>:<field>`.
+fn find_column(headers: &StringRecord, group: &str, field: &str) -> Option<usize> {
+    headers.iter().position(|header| {
+        header
+            .split_once(':')
+            .is_some_and(|(header_group, header_field)| {
+                header_field == field
+                    && header_group
+                        .split_once('(')
+                        .map_or(header_group, |(name, _)| name)
+                        == group
+            })
+    })
+}
+
+fn unix_millis(time: OffsetDateTime) -> Result<u64, Error> {
+    u64::try_from(time.unix_timestamp_nanos() / 1_000_000)
+        .map_err(|_| invalid(format!("time {} is before the UNIX epoch", time)))
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidInput {
+        format: FORMAT,
+        message: message.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample() {
+        let track =
+            read_datcon_track(include_str!("../testdata/datcon.csv").as_bytes(), 5).unwrap();
+
+        assert_eq!(track.uas_id, 5);
+        assert_eq!(track.name.as_deref(), Some("Ground truth (DATCON)"));
+        let home = track.uav_home_location.unwrap();
+        assert_eq!((home.lat, home.lon, home.height), (51.4506, 4.334, 12.5));
+
+        // Rows without a fix and repeated rows are skipped. Times are interpolated from the
+        // offsets, starting when `dateTimeStamp` changes to 10:00:01 at 11.1 seconds.
+        assert_eq!(
+            track
+                .records
+                .iter()
+                .map(|record| (record.record_number, record.time))
+                .collect::<Vec<_>>(),
+            [
+                (1, 1_682_935_200_100),
+                (2, 1_682_935_200_500),
+                (3, 1_682_935_201_000),
+            ]
+        );
+        let Location::Position3d(pos) = track.records[0].location else {
+            panic!("DatCon records must be read as Position3d");
+        };
+        assert_eq!(
+            (pos.lat, pos.lon, pos.height),
+            (51.4507167, 4.3341194, 32.5)
+        );
+        let velocity = track.records[1].velocity.unwrap();
+        assert_eq!(
+            (velocity.east, velocity.north, velocity.up),
+            (-0.5, 1.5, 0.5)
+        );
+    }
+
+    #[test]
+    fn missing_column() {
+        let csv = "offsetTime,GPS(0):Long,GPS(0):Lat,GPS:dateTimeStamp\n";
+        assert!(matches!(
+            read_datcon_track(csv.as_bytes(), 1),
+            Err(Error::InvalidInput { .. })
+        ));
+    }
+}
@@ -0,0 +1,345 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Seek},
+    marker::PhantomData,
+};
+
+use serde_json::Value;
+
+use crate::{
+    decompress,
+    error::JsonDiagnostic,
+    migrate_document,
+    schema::{scan_schema_version, SchemaVersion},
+    Database, Error,
+};
+
+/// Maximum number of characters shown at each side of the error position in
+/// [`JsonDiagnostic::snippet`].
+const SNIPPET_CONTEXT: usize = 40;
+
+/// Reads a COURAGEOUS document of any supported schema version from `reader`, returning the version
+/// it was detected as.
+///
+/// Documents older than the current schema version are converted with [`migrate_document`]. If the
+/// document cannot be read, the error is an [`Error::InvalidDocument`] describing where in the
+/// document it was found.
+///
+/// Documents compressed with gzip or zstd are decompressed transparently (See [`decompress`]).
+///
+/// The document is deserialized as it is read, without storing its text. `reader` is only read
+/// again if the document cannot be read as the current schema version: once more to detect its
+/// version, and once more to either migrate it or find the text around the error. Migrating a
+/// document loads it entirely into memory. See [`read_document_reopening`] for inputs that cannot
+/// seek, such as pipes, and [`stream_as_kml`](crate::stream_as_kml) for converting very large
+/// documents with bounded memory usage.
+pub fn read_document<R: Read + Seek>(reader: R) -> Result<(Database, SchemaVersion), Error> {
+    read_source(&mut Seekable(reader))
+}
+
+/// Like [`read_document`], but reads the input from `open` instead of seeking back to its start.
+///
+/// `open` is called once for each time the input is read: once if the document can be read as the
+/// current schema version, up to three times otherwise. Compressed inputs are decompressed
+/// transparently too.
+pub fn read_document_reopening<R: Read>(
+    open: impl FnMut() -> Result<R, Error>,
+) -> Result<(Database, SchemaVersion), Error> {
+    read_source(&mut Reopening(open, PhantomData))
+}
+
+fn read_source(source: &mut impl Source) -> Result<(Database, SchemaVersion), Error> {
+    let mut de = serde_json::Deserializer::from_reader(source.open()?);
+    let (path, err) = match serde_path_to_error::deserialize(&mut de) {
+        Ok(database) => match de.end() {
+            Ok(()) => return Ok((database, SchemaVersion::CURRENT)),
+            Err(err) => (".".to_owned(), err),
+        },
+        Err(err) => (err.path().to_string(), err.into_inner()),
+    };
+    drop(de);
+    if err.is_io() {
+        return Err(Error::Io(err.into()));
+    }
+
+    // Older schema versions are valid JSON with a different structure, so syntax errors cannot come
+    // from them, and reading the document again can be skipped
+    if err.is_data()
+        && matches!(scan_schema_version(source.open()?), Ok(version) if version < SchemaVersion::CURRENT)
+    {
+        let document = serde_json::from_reader::<_, Value>(source.open()?)?;
+        return migrate_document(document);
+    }
+    Err(invalid_document(
+        path,
+        err,
+        SchemaVersion::CURRENT,
+        Some(&mut source.open()?),
+    ))
+}
+
+/// An input that can be read from its start several times.
+pub(crate) trait Source {
+    /// Returns a reader of the input from its start, decompressing it if needed.
+    fn open(&mut self) -> Result<Box<dyn BufRead + '_>, Error>;
+}
+
+/// A [`Source`] that seeks back to the start of a reader.
+pub(crate) struct Seekable<R>(pub R);
+
+impl<R: Read + Seek> Source for Seekable<R> {
+    fn open(&mut self) -> Result<Box<dyn BufRead + '_>, Error> {
+        self.0.rewind()?;
+        Ok(decompress(BufReader::new(&mut self.0))?)
+    }
+}
+
+/// A [`Source`] that opens a new reader each time.
+pub(crate) struct Reopening<F, R>(pub F, pub PhantomData<fn() -> R>);
+
+impl<R: Read, F: FnMut() -> Result<R, Error>> Source for Reopening<F, R> {
+    fn open(&mut self) -> Result<Box<dyn BufRead + '_>, Error> {
+        Ok(decompress(BufReader::new((self.0)()?))?)
+    }
+}
+
+/// Converts an error found at `path` while deserializing a document into an
+/// [`Error::InvalidDocument`]. The snippet is read from `source` if given, which must read the
+/// document from its start.
+pub(crate) fn invalid_document(
+    path: String,
+    err: serde_json::Error,
+    version: SchemaVersion,
+    source: Option<&mut dyn BufRead>,
+) -> Error {
+    // Errors of documents deserialized from a `Value` have no position
+    let position = (err.line() != 0).then(|| (err.line(), err.column()));
+    let message = err.to_string();
+    let message = match position {
+        Some((line, column)) => message
+            .strip_suffix(&format!(" at line {} column {}", line, column))
+            .unwrap_or(&message)
+            .to_owned(),
+        None => message,
+    };
+
+    // The snippet is only a help, so failing to read it is not worth reporting
+    let (snippet, snippet_column) = match (source, position) {
+        (Some(source), Some((line, column))) => snippet(source, line, column)
+            .ok()
+            .flatten()
+            .map_or((None, 0), |(snippet, snippet_column)| {
+                (Some(snippet), snippet_column)
+            }),
+        _ => (None, 0),
+    };
+
+    Error::InvalidDocument(Box::new(JsonDiagnostic {
+        version,
+        path,
+        position,
+        message,
+        snippet,
+        snippet_column,
+    }))
+}
+
+/// Returns the text of line `line` of `reader` around `column` (Both starting at 1, the column in
+/// bytes) and the position of `column` inside it, in characters. Returns `None` if `reader` ends
+/// before that line.
+///
+/// Only the bytes around `column` are kept, since minified documents are a single huge line.
+fn snippet(
+    mut reader: impl BufRead,
+    line: usize,
+    column: usize,
+) -> io::Result<Option<(String, usize)>> {
+    // Enough bytes for `SNIPPET_CONTEXT` characters of up to 4 bytes, plus a character cut in half
+    const WINDOW: usize = SNIPPET_CONTEXT * 4 + 4;
+
+    for _ in 1..line {
+        if !skip_line(&mut reader)? {
+            return Ok(None);
+        }
+    }
+
+    let error_idx = column.saturating_sub(1);
+    let start = error_idx.saturating_sub(WINDOW);
+    let end = error_idx + WINDOW;
+    // The bytes of the line from `start` to `end`, and whether the line goes on after `end`
+    let mut window = Vec::new();
+    let mut cut_end = false;
+    let mut idx = 0;
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        let len = buf.iter().position(|&b| b == b'\n').unwrap_or(buf.len());
+        let chunk = &buf[..len];
+        let from = start.saturating_sub(idx).min(len);
+        let to = end.saturating_sub(idx).min(len);
+        window.extend_from_slice(&chunk[from..to]);
+        if to < len {
+            cut_end = true;
+            break;
+        }
+        let end_of_line = len < buf.len();
+        reader.consume(len);
+        idx += len;
+        if end_of_line {
+            break;
+        }
+    }
+    if !cut_end && window.last() == Some(&b'\r') {
+        window.pop();
+    }
+
+    let error_idx = (error_idx - start).min(window.len());
+    let before = String::from_utf8_lossy(&window[..error_idx]);
+    let after = String::from_utf8_lossy(&window[error_idx..]);
+    let before_len = before.chars().count();
+    let skipped = before_len.saturating_sub(SNIPPET_CONTEXT);
+
+    let mut snippet: String = before.chars().skip(skipped).collect();
+    let mut snippet_column = before_len - skipped;
+    snippet.extend(after.chars().take(SNIPPET_CONTEXT));
+    if after.chars().count() > SNIPPET_CONTEXT || cut_end {
+        snippet.push_str("...");
+    }
+    if skipped > 0 || start > 0 {
+        snippet.insert_str(0, "...");
+        snippet_column += 3;
+    }
+    Ok(Some((snippet, snippet_column)))
+}
+
+/// Skips the bytes of `reader` up to the next line, returning whether there is one.
+fn skip_line(reader: &mut impl BufRead) -> io::Result<bool> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(false);
+        }
+        match buf.iter().position(|&b| b == b'\n') {
+            Some(idx) => {
+                reader.consume(idx + 1);
+                return Ok(true);
+            }
+            None => {
+                let len = buf.len();
+                reader.consume(len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use super::*;
+    use crate::schema::tests::{downgrade, v0_4_0_document};
+
+    #[test]
+    fn read_versions() {
+        for version in [
+            SchemaVersion::V0_3_0,
+            SchemaVersion::V0_3_1,
+            SchemaVersion::V0_4_0,
+        ] {
+            let mut document = v0_4_0_document();
+            if version < SchemaVersion::CURRENT {
+                document = downgrade(document, version);
+            }
+            let json = serde_json::to_vec_pretty(&document).unwrap();
+            let (database, detected) = read_document(Cursor::new(&json)).unwrap();
+            assert_eq!(detected, version);
+            assert_eq!(database.tracks[0].records.len(), 2);
+        }
+    }
+
+    #[test]
+    fn invalid_current_document() {
+        let mut document = v0_4_0_document();
+        document["tracks"][0]["records"][1]["time"] = Value::from("noon");
+        let json = serde_json::to_vec_pretty(&document).unwrap();
+
+        let Err(Error::InvalidDocument(diagnostic)) = read_document(Cursor::new(&json)) else {
+            panic!("invalid documents must fail with a diagnostic");
+        };
+        // Not mistaken for an older schema version
+        assert_eq!(diagnostic.version, SchemaVersion::CURRENT);
+        assert_eq!(diagnostic.path, "tracks[0].records[1].time");
+        assert!(diagnostic.position.is_some());
+        assert!(diagnostic.snippet.as_deref().unwrap().contains("\"noon\""));
+    }
+
+    #[test]
+    fn invalid_older_document() {
+        let mut document = downgrade(v0_4_0_document(), SchemaVersion::V0_3_1);
+        document["tracks"][0]["records"][1]["time"] = Value::from("noon");
+        let json = serde_json::to_vec(&document).unwrap();
+
+        let Err(Error::InvalidDocument(diagnostic)) = read_document(Cursor::new(&json)) else {
+            panic!("invalid documents must fail with a diagnostic");
+        };
+        assert_eq!(diagnostic.version, SchemaVersion::V0_3_1);
+        assert_eq!(diagnostic.path, "tracks[0].records[1].time");
+    }
+
+    #[test]
+    fn reopening() {
+        let read = |document: Value| {
+            let json = serde_json::to_vec(&document).unwrap();
+            let mut opened = 0;
+            let result = read_document_reopening(|| {
+                opened += 1;
+                Ok(&json[..])
+            });
+            (result, opened)
+        };
+
+        let (result, opened) = read(v0_4_0_document());
+        assert_eq!(result.unwrap().1, SchemaVersion::CURRENT);
+        assert_eq!(opened, 1);
+        let (result, opened) = read(downgrade(v0_4_0_document(), SchemaVersion::V0_3_0));
+        assert_eq!(result.unwrap().1, SchemaVersion::V0_3_0);
+        assert_eq!(opened, 3);
+        let mut document = v0_4_0_document();
+        document["tracks"][0]["uas_id"] = Value::from("seven");
+        let (result, opened) = read(document);
+        assert!(matches!(result, Err(Error::InvalidDocument(_))));
+        assert_eq!(opened, 3);
+    }
+
+    #[test]
+    fn compressed() {
+        let json =
+            serde_json::to_vec(&downgrade(v0_4_0_document(), SchemaVersion::V0_3_1)).unwrap();
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(&json).unwrap();
+        let gzip = gzip.finish().unwrap();
+
+        let (_, version) = read_document(Cursor::new(gzip)).unwrap();
+        assert_eq!(version, SchemaVersion::V0_3_1);
+    }
+
+    #[test]
+    fn long_line_snippet() {
+        let line: String = (0..1000)
+            .map(|idx| char::from(b'a' + (idx % 26) as u8))
+            .collect();
+        let text = format!("{{\n{}\r\n}}", line);
+
+        let (shown, column) = snippet(text.as_bytes(), 2, 501).unwrap().unwrap();
+        assert_eq!(shown.chars().count(), 3 + SNIPPET_CONTEXT * 2 + 3);
+        assert!(shown.starts_with("...") && shown.ends_with("..."));
+        assert_eq!(shown.chars().nth(column), line.chars().nth(500));
+
+        // The end of the line is shown as is
+        let (shown, column) = snippet(text.as_bytes(), 2, 990).unwrap().unwrap();
+        assert!(shown.ends_with(&line[980..]));
+        assert_eq!(shown.chars().nth(column), line.chars().nth(989));
+        assert_eq!(snippet(text.as_bytes(), 4, 1).unwrap(), None);
+    }
+}
@@ -0,0 +1,122 @@
+use std::fmt::Display;
+
+use courageous_format::Position3d;
+
+use crate::SchemaVersion;
+
+/// Identifies the set a record belongs to, by its index inside [`Database::tracks`] or
+/// [`Database::detection`].
+///
+/// [`Database::tracks`]: crate::Database::tracks
+/// [`Database::detection`]: crate::Database::detection
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordSet {
+    Track(usize),
+    Detection(usize),
+}
+
+impl Display for RecordSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordSet::Track(idx) => write!(f, "track #{}", idx),
+            RecordSet::Detection(idx) => write!(f, "detection set #{}", idx),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("XML error: {0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("KMZ archive error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("could not format time: {0}")]
+    TimeFormat(#[from] time::error::Format),
+    #[error("invalid timestamp {time} in record {record_number} of {set}")]
+    InvalidTimestamp {
+        set: RecordSet,
+        record_number: u64,
+        time: u64,
+    },
+    #[error("stale time of record {record_number} of {set} is out of range")]
+    InvalidStaleTime { set: RecordSet, record_number: u64 },
+    #[error(
+        "invalid coordinate (lat {}, lon {}) in record {record_number} of {set}",
+        position.lat,
+        position.lon
+    )]
+    InvalidCoordinate {
+        set: RecordSet,
+        record_number: u64,
+        position: Position3d,
+    },
+    #[error(
+        "invalid static CUAS location (lat {}, lon {})",
+        .0.lat,
+        .0.lon
+    )]
+    InvalidCuasLocation(Position3d),
+    #[error("invalid KML: {0}")]
+    InvalidKml(String),
+    #[error("invalid {format} file: {message}")]
+    InvalidInput {
+        format: &'static str,
+        message: String,
+    },
+    #[error("{0}")]
+    InvalidDocument(Box<JsonDiagnostic>),
+}
+
+/// Describes why and where a COURAGEOUS document could not be read.
+///
+/// Its [`Display`] implementation shows the line the error was found on, if known, below the
+/// message.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct JsonDiagnostic {
+    /// The schema version the document was read as.
+    pub version: SchemaVersion,
+    /// Path of the value that could not be read, e.g. `tracks[12].records[4031].location`.
+    pub path: String,
+    /// Line and column (Starting at 1) the error was found on. Unknown for documents that had to be
+    /// migrated from an older schema version.
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+    /// The text around the error position, up to a few dozen characters.
+    pub snippet: Option<String>,
+    /// Column of the error inside `snippet`, starting at 0.
+    pub snippet_column: usize,
+}
+
+impl Display for JsonDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid COURAGEOUS ({}) document at {}",
+            self.version, self.path
+        )?;
+        if let Some((line, column)) = self.position {
+            write!(f, " (line {}, column {})", line, column)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let (Some(snippet), Some((line, _))) = (&self.snippet, self.position) {
+            let gutter = line.to_string();
+            write!(
+                f,
+                "\n {} | {}\n {} | {}^",
+                gutter,
+                snippet,
+                " ".repeat(gutter.len()),
+                " ".repeat(self.snippet_column)
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+use std::io::Write;
+
+use courageous_format::{
+    Alarm, Arc, Classification, Detection, Location, Position3d, Track, TrackingRecord, Velocity,
+};
+use serde_json::{json, Map, Value};
+use time::format_description::well_known::Rfc3339;
+
+use crate::{
+    kml::geometry::{arc_sector, distance_from_position, quad_bearings},
+    record::{check_cuas_location, check_record_position, classification_str, record_time},
+    Database, Error, RecordSet,
+};
+
+#[derive(Clone, Default)]
+#[non_exhaustive]
+pub struct WriteAsGeoJsonOptions {
+    pub cuas_range: f64,
+    /// Overrides the `static_cuas_location` member of the database if given.
+    pub static_cuas_location: Option<Position3d>,
+}
+
+impl WriteAsGeoJsonOptions {
+    pub fn cuas_range(mut self, val: f64) -> WriteAsGeoJsonOptions {
+        self.cuas_range = val;
+        self
+    }
+    pub fn static_cuas_location(mut self, val: Option<Position3d>) -> WriteAsGeoJsonOptions {
+        self.static_cuas_location = val;
+        self
+    }
+}
+
+/// Writes `database` as a GeoJSON FeatureCollection.
+///
+/// Every feature has a `feature_type` property, which is one of:
+/// - `track`: Consecutive positional records of a track with the same classification, as a
+///   LineString (Or a Point if there is only one). As in KML, records without a position split
+///   tracks into several features. Timestamps and record numbers of each vertex are given in the
+///   `coordTimes` and `record_numbers` properties.
+/// - `track_record`: A track record without an absolute position (e.g. a bearing ray).
+/// - `detection_record`: A record of a detection set.
+/// - `uav_home_location` and `cuas_location`: Points.
+pub fn write_as_geojson(
+    database: Database,
+    writer: impl Write,
+    options: WriteAsGeoJsonOptions,
+) -> Result<(), Error> {
+    let static_cuas_location = options
+        .static_cuas_location
+        .unwrap_or(database.static_cuas_location);
+    check_cuas_location(static_cuas_location)?;
+    let ctx = Context {
+        static_cuas_location,
+        cuas_range: options.cuas_range,
+    };
+
+    let mut features = Vec::new();
+    for (idx, detection) in database.detection.iter().enumerate() {
+        detection_features(&mut features, detection, idx, &ctx)?;
+    }
+    for (idx, track) in database.tracks.iter().enumerate() {
+        track_features(&mut features, track, idx, &ctx)?;
+    }
+    features.push(feature(
+        point(static_cuas_location),
+        properties([("feature_type", json!("cuas_location"))]),
+    ));
+
+    serde_json::to_writer(
+        writer,
+        &json!({
+            "type": "FeatureCollection",
+            "features": features,
+        }),
+    )?;
+    Ok(())
+}
+
+struct Context {
+    static_cuas_location: Position3d,
+    cuas_range: f64,
+}
+
+fn detection_features(
+    features: &mut Vec<Value>,
+    detection: &Detection,
+    idx: usize,
+    ctx: &Context,
+) -> Result<(), Error> {
+    let set = RecordSet::Detection(idx);
+    let set_properties = [
+        ("detection_set", json!(idx)),
+        ("uas_id", json!(detection.uas_id)),
+        ("name", json!(detection.name)),
+    ];
+
+    if let Some(uav_home_location) = detection.uav_home_location {
+        let mut props = properties(set_properties.clone());
+        props.insert("feature_type".to_owned(), json!("uav_home_location"));
+        features.push(feature(point(uav_home_location), props));
+    }
+
+    for record in &detection.records {
+        let Some(location) = record.location else {
+            continue;
+        };
+        let geometry = location_geometry(
+            location,
+            record.cuas_location,
+            set,
+            record.record_number,
+            ctx,
+        )?;
+        let mut props = properties(set_properties.clone());
+        props.insert("feature_type".to_owned(), json!("detection_record"));
+        props.extend(record_properties(
+            record.record_number,
+            record_time(record.time, set, record.record_number)?.format(&Rfc3339)?,
+            record.classification,
+            record.alarm,
+            record.identification.as_deref(),
+            record.velocity,
+        ));
+        features.push(feature(geometry, props));
+    }
+
+    Ok(())
+}
+
+fn track_features(
+    features: &mut Vec<Value>,
+    track: &Track,
+    idx: usize,
+    ctx: &Context,
+) -> Result<(), Error> {
+    let set = RecordSet::Track(idx);
+    let set_properties = [
+        ("track", json!(idx)),
+        ("uas_id", json!(track.uas_id)),
+        ("name", json!(track.name)),
+    ];
+
+    if let Some(uav_home_location) = track.uav_home_location {
+        let mut props = properties(set_properties.clone());
+        props.insert("feature_type".to_owned(), json!("uav_home_location"));
+        features.push(feature(point(uav_home_location), props));
+    }
+
+    const CLASSIFICATIONS: [Classification; 4] = [
+        Classification::Uav,
+        Classification::Gcs,
+        Classification::Other,
+        Classification::Unknown,
+    ];
+    let mut pos_runs = Vec::new();
+    for classification in CLASSIFICATIONS {
+        let mut run = Vec::new();
+        for record in track
+            .records
+            .iter()
+            .filter(|r| r.classification == classification)
+        {
+            match record_position(record) {
+                Some(pos) => run.push((record, pos)),
+                None if !run.is_empty() => pos_runs.push(std::mem::take(&mut run)),
+                None => {}
+            }
+        }
+        if !run.is_empty() {
+            pos_runs.push(run);
+        }
+    }
+
+    for pos_records in pos_runs {
+        let classification = pos_records[0].0.classification;
+        let mut coordinates = Vec::with_capacity(pos_records.len());
+        let mut times = Vec::with_capacity(pos_records.len());
+        for &(record, pos) in &pos_records {
+            coordinates.push(position(check_record_position(
+                pos,
+                set,
+                record.record_number,
+            )?));
+            times.push(record_time(record.time, set, record.record_number)?.format(&Rfc3339)?);
+        }
+        // A LineString requires at least two positions
+        let geometry = if coordinates.len() == 1 {
+            json!({ "type": "Point", "coordinates": coordinates[0] })
+        } else {
+            json!({ "type": "LineString", "coordinates": coordinates })
+        };
+
+        let mut props = properties(set_properties.clone());
+        props.insert("feature_type".to_owned(), json!("track"));
+        props.insert(
+            "classification".to_owned(),
+            json!(classification_str(classification)),
+        );
+        props.insert("coordTimes".to_owned(), json!(times));
+        props.insert(
+            "record_numbers".to_owned(),
+            pos_records
+                .iter()
+                .map(|(record, _)| record.record_number)
+                .collect(),
+        );
+        features.push(feature(geometry, props));
+    }
+
+    for record in track
+        .records
+        .iter()
+        .filter(|r| record_position(r).is_none())
+    {
+        let geometry = location_geometry(
+            record.location,
+            record.cuas_location,
+            set,
+            record.record_number,
+            ctx,
+        )?;
+        let mut props = properties(set_properties.clone());
+        props.insert("feature_type".to_owned(), json!("track_record"));
+        props.extend(record_properties(
+            record.record_number,
+            record_time(record.time, set, record.record_number)?.format(&Rfc3339)?,
+            record.classification,
+            Some(record.alarm),
+            record.identification.as_deref(),
+            record.velocity,
+        ));
+        features.push(feature(geometry, props));
+    }
+
+    Ok(())
+}
+
+fn record_position(record: &TrackingRecord) -> Option<Position3d> {
+    match record.location {
+        Location::Position3d(pos) => Some(pos),
+        Location::Position2d(pos) => Some(Position3d {
+            lat: pos.lat,
+            lon: pos.lon,
+            height: 0.,
+        }),
+        _ => None,
+    }
+}
+
+fn location_geometry(
+    location: Location,
+    cuas_location: Option<Position3d>,
+    set: RecordSet,
+    record_number: u64,
+    ctx: &Context,
+) -> Result<Value, Error> {
+    let cuas_origin = cuas_location.unwrap_or(ctx.static_cuas_location);
+    let ray = |bearing: f64, elevation: f64| {
+        let target = distance_from_position(cuas_origin, bearing, elevation, ctx.cuas_range);
+        json!({
+            "type": "LineString",
+            "coordinates": [position(cuas_origin), position(target)],
+        })
+    };
+    let sector = |bearing_from: f64, bearing_to: f64| {
+        let mut ring = arc_sector(bearing_from, bearing_to, cuas_origin, ctx.cuas_range);
+        // GeoJSON requires exterior rings to be closed and counterclockwise
+        ring.push(ring[0]);
+        let signed_area: f64 = ring
+            .windows(2)
+            .map(|w| w[0].lon * w[1].lat - w[1].lon * w[0].lat)
+            .sum();
+        if signed_area < 0. {
+            ring.reverse();
+        }
+        json!({
+            "type": "Polygon",
+            "coordinates": [ring.into_iter().map(position).collect::<Vec<_>>()],
+        })
+    };
+
+    Ok(match location {
+        Location::Position3d(pos) => point(check_record_position(pos, set, record_number)?),
+        Location::Position2d(pos) => {
+            check_record_position(
+                Position3d {
+                    lat: pos.lat,
+                    lon: pos.lon,
+                    height: 0.,
+                },
+                set,
+                record_number,
+            )?;
+            json!({ "type": "Point", "coordinates": [pos.lon, pos.lat] })
+        }
+        Location::BearingElevationDistance {
+            bearing,
+            elevation,
+            distance,
+        } => point(distance_from_position(
+            cuas_origin,
+            bearing,
+            elevation,
+            distance,
+        )),
+        Location::BearingElevation { bearing, elevation } => ray(bearing, elevation),
+        Location::Bearing { bearing } => ray(bearing, 0.),
+        Location::Quad { quad } => {
+            let (bearing_from, bearing_to) = quad_bearings(quad);
+            sector(bearing_from, bearing_to)
+        }
+        Location::Arc(Arc { from, to }) => sector(from, to),
+    })
+}
+
+fn record_properties(
+    record_number: u64,
+    time: String,
+    classification: Classification,
+    alarm: Option<Alarm>,
+    identification: Option<&str>,
+    velocity: Option<Velocity>,
+) -> Map<String, Value> {
+    properties([
+        ("record_number", json!(record_number)),
+        ("time", json!(time)),
+        ("classification", json!(classification_str(classification))),
+        ("alarm", json!(alarm.is_some_and(|a| a.active))),
+        ("alarm_certainty", json!(alarm.map(|a| a.certainty))),
+        ("identification", json!(identification)),
+        (
+            "velocity",
+            json!(velocity.map(|v| json!({ "east": v.east, "north": v.north, "up": v.up }))),
+        ),
+    ])
+}
+
+fn properties<const N: usize>(props: [(&str, Value); N]) -> Map<String, Value> {
+    props
+        .into_iter()
+        .map(|(key, value)| (key.to_owned(), value))
+        .collect()
+}
+
+fn feature(geometry: Value, properties: Map<String, Value>) -> Value {
+    json!({
+        "type": "Feature",
+        "geometry": geometry,
+        "properties": properties,
+    })
+}
+
+fn point(pos: Position3d) -> Value {
+    json!({ "type": "Point", "coordinates": position(pos) })
+}
+
+fn position(pos: Position3d) -> [f64; 3] {
+    [pos.lon, pos.lat, pos.height]
+}
@@ -0,0 +1,198 @@
+use std::io::Write;
+
+use courageous_format::{Alarm, Classification, Detection, Location, Position3d, Track};
+use quick_xml::{
+    events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event},
+    Writer,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    kml::serialize::write_element,
+    record::{check_record_position, classification_str, record_time},
+    Database, Error, RecordSet,
+};
+
+const GPX_ATTRIBUTES: [(&str, &str); 4] = [
+    ("version", "1.1"),
+    ("creator", "track2kml"),
+    ("xmlns", "http://www.topografix.com/GPX/1/1"),
+    (
+        "xmlns:track2kml",
+        "https://github.com/COURAGEOUS-isf/track2kml",
+    ),
+];
+
+/// Writes the absolute positions of `database` as a GPX 1.1 document.
+///
+/// Each track becomes a `<trk>`, with a new `<trkseg>` every time the classification of its
+/// records changes. Records of detection sets become waypoints. Records without an absolute position
+/// cannot be represented in GPX and are skipped.
+///
+/// The record number, classification, alarm and identification of each point are written into its
+/// `<extensions>`, in the `track2kml` namespace.
+pub fn write_as_gpx(database: Database, writer: impl Write) -> Result<(), Error> {
+    let mut xml = Writer::new(writer);
+    xml.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    xml.write_event(Event::Start(
+        BytesStart::new("gpx").with_attributes(GPX_ATTRIBUTES),
+    ))?;
+    // GPX requires all waypoints to come before any track
+    for (idx, detection) in database.detection.iter().enumerate() {
+        write_waypoints(&mut xml, detection, idx)?;
+    }
+    for (idx, track) in database.tracks.iter().enumerate() {
+        write_track(&mut xml, track, idx)?;
+    }
+    xml.write_event(Event::End(BytesEnd::new("gpx")))?;
+
+    Ok(())
+}
+
+/// The members of tracking and detection records written into GPX points.
+struct PointData<'a> {
+    position: Position3d,
+    /// Whether the height of `position` is known.
+    has_height: bool,
+    time: OffsetDateTime,
+    record_number: u64,
+    classification: Classification,
+    alarm: Option<Alarm>,
+    identification: Option<&'a str>,
+}
+
+fn write_waypoints(
+    x: &mut Writer<impl Write>,
+    detection: &Detection,
+    idx: usize,
+) -> Result<(), Error> {
+    let set = RecordSet::Detection(idx);
+    let set_name = detection.name.as_deref().unwrap_or("Unnamed detection set");
+    for record in &detection.records {
+        let Some((position, has_height)) = record.location.and_then(absolute_position) else {
+            continue;
+        };
+        let point = PointData {
+            position: check_record_position(position, set, record.record_number)?,
+            has_height,
+            time: record_time(record.time, set, record.record_number)?,
+            record_number: record.record_number,
+            classification: record.classification,
+            alarm: record.alarm,
+            identification: record.identification.as_deref(),
+        };
+        write_point(
+            x,
+            "wpt",
+            &point,
+            Some(&format!("{} #{}", set_name, record.record_number)),
+        )?;
+    }
+    Ok(())
+}
+
+fn write_track(x: &mut Writer<impl Write>, track: &Track, idx: usize) -> Result<(), Error> {
+    let set = RecordSet::Track(idx);
+    let mut points = Vec::new();
+    for record in &track.records {
+        let Some((position, has_height)) = absolute_position(record.location) else {
+            continue;
+        };
+        points.push(PointData {
+            position: check_record_position(position, set, record.record_number)?,
+            has_height,
+            time: record_time(record.time, set, record.record_number)?,
+            record_number: record.record_number,
+            classification: record.classification,
+            alarm: Some(record.alarm),
+            identification: record.identification.as_deref(),
+        });
+    }
+    if points.is_empty() {
+        return Ok(());
+    }
+
+    let name = track
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("Unnamed track (UAS ID {})", track.uas_id));
+    write_element(x, "trk", |x| {
+        x.create_element("name")
+            .write_text_content(BytesText::new(&name))?;
+        let mut segment_start = 0;
+        while segment_start < points.len() {
+            let classification = points[segment_start].classification;
+            let segment_len = points[segment_start..]
+                .iter()
+                .take_while(|point| point.classification == classification)
+                .count();
+            write_element(x, "trkseg", |x| {
+                for point in &points[segment_start..segment_start + segment_len] {
+                    write_point(x, "trkpt", point, None)?;
+                }
+                Ok(())
+            })?;
+            segment_start += segment_len;
+        }
+        Ok(())
+    })
+}
+
+fn write_point(
+    x: &mut Writer<impl Write>,
+    element: &str,
+    point: &PointData,
+    name: Option<&str>,
+) -> Result<(), Error> {
+    let time = point.time.format(&Rfc3339)?;
+    x.create_element(element)
+        .with_attribute(("lat", point.position.lat.to_string().as_str()))
+        .with_attribute(("lon", point.position.lon.to_string().as_str()))
+        .write_inner_content(|x| {
+            if point.has_height {
+                x.create_element("ele")
+                    .write_text_content(BytesText::new(&point.position.height.to_string()))?;
+            }
+            x.create_element("time")
+                .write_text_content(BytesText::new(&time))?;
+            if let Some(name) = name {
+                x.create_element("name")
+                    .write_text_content(BytesText::new(name))?;
+            }
+            x.create_element("extensions").write_inner_content(|x| {
+                x.create_element("track2kml:record_number")
+                    .write_text_content(BytesText::new(&point.record_number.to_string()))?;
+                x.create_element("track2kml:classification")
+                    .write_text_content(BytesText::new(classification_str(point.classification)))?;
+                if let Some(alarm) = point.alarm {
+                    x.create_element("track2kml:alarm")
+                        .write_text_content(BytesText::new(&alarm.active.to_string()))?;
+                    x.create_element("track2kml:alarm_certainty")
+                        .write_text_content(BytesText::new(&alarm.certainty.to_string()))?;
+                }
+                if let Some(identification) = point.identification {
+                    x.create_element("track2kml:identification")
+                        .write_text_content(BytesText::new(identification))?;
+                }
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+    Ok(())
+}
+
+/// Returns the position of `location` and whether its height is known, if it is absolute.
+fn absolute_position(location: Location) -> Option<(Position3d, bool)> {
+    match location {
+        Location::Position3d(pos) => Some((pos, true)),
+        Location::Position2d(pos) => Some((
+            Position3d {
+                lat: pos.lat,
+                lon: pos.lon,
+                height: 0.,
+            },
+            false,
+        )),
+        _ => None,
+    }
+}
@@ -0,0 +1,325 @@
+use std::io::BufRead;
+
+use courageous_format::{Alarm, Classification, Location, Position3d, Track, TrackingRecord};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{Database, Error};
+
+const FORMAT: &str = "HGH log";
+
+/// Converts a track log of an HGH infrared camera into a [`Database`], detecting which of the two
+/// iterations of the format it uses.
+///
+/// The format of these logs is not published, and no real log was available when this reader was
+/// written, so it assumes the structures below. Both start with comment lines (Starting with `#`),
+/// which may give the position of the camera as latitude, longitude and height (AMSL), followed by
+/// a header naming the columns of the semicolon-separated rows after it. `testdata/hgh_v1.log` and
+/// `testdata/hgh_v2.log` are samples of them.
+///
+/// The first iteration gives RFC 3339 times:
+/// ```text
+/// # Sensor position: 51.45;4.33;15
+/// Time;Track;Azimuth;Elevation
+/// 2023-05-01T10:00:00.250Z;12;45.5;2.25
+/// ```
+///
+/// The second one is detected by its `Timestamp` column, which gives times as microseconds since
+/// the UNIX epoch rather than milliseconds, and adds an optional `Class` column:
+/// ```text
+/// # Sensor position: 51.45;4.33;15
+/// Timestamp;Track;Azimuth;Elevation;Class
+/// 1682935200250000;12;45.5;2.25;UAV
+/// ```
+///
+/// Rows become `BearingElevation` records (Azimuth and elevation are given in degrees) of a
+/// [`Track`] with the track number as UAS ID, in the order tracks first appear. Records are
+/// numbered in the order they appear in the log. Classes are mapped ignoring case: `uav` and
+/// `drone` to [`Classification::Uav`], `gcs` and `unknown` to their equivalent classification, and
+/// every other class to [`Classification::Other`]. Rows without one are classified as
+/// [`Classification::Unknown`].
+///
+/// `static_cuas_location`, if given, overrides the position of the camera given by the log. One of
+/// them is required.
+pub fn read_hgh_log(
+    reader: impl BufRead,
+    static_cuas_location: Option<Position3d>,
+) -> Result<Database, Error> {
+    let mut sensor_position = None;
+    let mut columns = None;
+    let mut tracks: Vec<Track> = Vec::new();
+    let mut record_number = 0;
+
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_number = idx + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            if let Some(position) = comment.trim().strip_prefix("Sensor position:") {
+                sensor_position = Some(parse_position(position, line_number)?);
+            }
+            continue;
+        }
+        let fields: Vec<&str> = line.split(';').map(str::trim).collect();
+        let Some(columns) = &columns else {
+            columns = Some(Columns::from_header(&fields, line_number)?);
+            continue;
+        };
+
+        let field = |column: usize| {
+            fields
+                .get(column)
+                .copied()
+                .ok_or_else(|| invalid(line_number, "missing columns"))
+        };
+        let number = |column: usize, name: &str| {
+            let value = field(column)?;
+            value
+                .parse::<f64>()
+                .map_err(|_| invalid(line_number, format!("invalid {} {:?}", name, value)))
+        };
+        let time = match columns.version {
+            Version::V1 => {
+                let time = field(columns.time)?;
+                let time = OffsetDateTime::parse(time, &Rfc3339).map_err(|err| {
+                    invalid(line_number, format!("invalid time {:?}: {}", time, err))
+                })?;
+                u64::try_from(time.unix_timestamp_nanos() / 1_000_000).map_err(|_| {
+                    invalid(
+                        line_number,
+                        format!("time {} is before the UNIX epoch", time),
+                    )
+                })?
+            }
+            Version::V2 => {
+                let timestamp = field(columns.time)?;
+                let micros = timestamp.parse::<u64>().map_err(|_| {
+                    invalid(line_number, format!("invalid timestamp {:?}", timestamp))
+                })?;
+                micros / 1000
+            }
+        };
+        let track_number = field(columns.track)?;
+        let uas_id = track_number.parse().map_err(|_| {
+            invalid(
+                line_number,
+                format!("invalid track number {:?}", track_number),
+            )
+        })?;
+        let classification = match columns.class.map(field).transpose()? {
+            None | Some("") => Classification::Unknown,
+            Some(class) => match class.to_lowercase().as_str() {
+                "uav" | "drone" => Classification::Uav,
+                "gcs" => Classification::Gcs,
+                "unknown" => Classification::Unknown,
+                _ => Classification::Other,
+            },
+        };
+
+        record_number += 1;
+        let record = TrackingRecord {
+            time,
+            record_number,
+            classification,
+            alarm: Alarm {
+                active: false,
+                certainty: 0.,
+            },
+            location: Location::BearingElevation {
+                bearing: number(columns.azimuth, "azimuth")?,
+                elevation: number(columns.elevation, "elevation")?,
+            },
+            identification: None,
+            velocity: None,
+            cuas_location: None,
+        };
+        match tracks.iter_mut().find(|track| track.uas_id == uas_id) {
+            Some(track) => track.records.push(record),
+            None => tracks.push(Track {
+                uas_id,
+                name: None,
+                records: vec![record],
+                uav_home_location: None,
+            }),
+        }
+    }
+
+    if columns.is_none() {
+        return Err(Error::InvalidInput {
+            format: FORMAT,
+            message: "the log has no column header".to_owned(),
+        });
+    }
+    let static_cuas_location =
+        static_cuas_location
+            .or(sensor_position)
+            .ok_or_else(|| Error::InvalidInput {
+                format: FORMAT,
+                message: "the log does not give the position of the camera".to_owned(),
+            })?;
+
+    Ok(Database {
+        detection: Vec::new(),
+        tracks,
+        static_cuas_location,
+    })
+}
+
+#[derive(Clone, Copy)]
+enum Version {
+    V1,
+    V2,
+}
+
+/// Indices of the columns of a log.
+struct Columns {
+    version: Version,
+    time: usize,
+    track: usize,
+    azimuth: usize,
+    elevation: usize,
+    class: Option<usize>,
+}
+
+impl Columns {
+    fn from_header(header: &[&str], line_number: usize) -> Result<Columns, Error> {
+        let column = |name: &str| header.iter().position(|column| *column == name);
+        let required = |name: &str| {
+            column(name).ok_or_else(|| invalid(line_number, format!("missing {} column", name)))
+        };
+        let (version, time) = match (column("Time"), column("Timestamp")) {
+            (Some(time), None) => (Version::V1, time),
+            (None, Some(timestamp)) => (Version::V2, timestamp),
+            _ => {
+                return Err(invalid(
+                    line_number,
+                    "expected either a Time or a Timestamp column",
+                ))
+            }
+        };
+        Ok(Columns {
+            version,
+            time,
+            track: required("Track")?,
+            azimuth: required("Azimuth")?,
+            elevation: required("Elevation")?,
+            class: match version {
+                Version::V1 => None,
+                Version::V2 => column("Class"),
+            },
+        })
+    }
+}
+
+fn parse_position(text: &str, line_number: usize) -> Result<Position3d, Error> {
+    let values = text
+        .split(';')
+        .map(|value| value.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>();
+    match values.as_deref() {
+        Ok(&[lat, lon, height]) => Ok(Position3d { lat, lon, height }),
+        _ => Err(invalid(
+            line_number,
+            format!("invalid sensor position {:?}", text.trim()),
+        )),
+    }
+}
+
+fn invalid(line_number: usize, message: impl Into<String>) -> Error {
+    Error::InvalidInput {
+        format: FORMAT,
+        message: format!("line {}: {}", line_number, message.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bearing_elevation(location: Location) -> (f64, f64) {
+        match location {
+            Location::BearingElevation { bearing, elevation } => (bearing, elevation),
+            _ => panic!("HGH records must be read as BearingElevation"),
+        }
+    }
+
+    #[test]
+    fn v1() {
+        let database =
+            read_hgh_log(include_str!("../testdata/hgh_v1.log").as_bytes(), None).unwrap();
+
+        let location = database.static_cuas_location;
+        assert_eq!(
+            (location.lat, location.lon, location.height),
+            (51.45, 4.33, 15.)
+        );
+        assert_eq!(
+            database
+                .tracks
+                .iter()
+                .map(|track| (track.uas_id, track.records.len()))
+                .collect::<Vec<_>>(),
+            [(12, 2), (13, 1)]
+        );
+        let record = &database.tracks[0].records[0];
+        assert_eq!(
+            (record.record_number, record.time, record.classification),
+            (1, 1_682_935_200_250, Classification::Unknown)
+        );
+        assert_eq!(bearing_elevation(record.location), (45.5, 2.25));
+        assert_eq!(database.tracks[1].records[0].record_number, 2);
+    }
+
+    #[test]
+    fn v2() {
+        let database =
+            read_hgh_log(include_str!("../testdata/hgh_v2.log").as_bytes(), None).unwrap();
+
+        assert_eq!(database.tracks.len(), 2);
+        // Timestamps are given in microseconds
+        assert_eq!(
+            database.tracks[0]
+                .records
+                .iter()
+                .map(|record| (record.time, record.classification))
+                .collect::<Vec<_>>(),
+            [
+                (1_682_935_200_250, Classification::Uav),
+                (1_682_935_201_250, Classification::Uav),
+            ]
+        );
+        assert_eq!(
+            bearing_elevation(database.tracks[0].records[1].location),
+            (46., 2.5)
+        );
+        assert_eq!(
+            database.tracks[1].records[0].classification,
+            Classification::Other
+        );
+    }
+
+    #[test]
+    fn sensor_position() {
+        let log = "Time;Track;Azimuth;Elevation\n2023-05-01T10:00:00Z;1;10;1\n";
+        assert!(matches!(
+            read_hgh_log(log.as_bytes(), None),
+            Err(Error::InvalidInput { .. })
+        ));
+
+        let origin = Position3d {
+            lat: 40.,
+            lon: -3.,
+            height: 600.,
+        };
+        let database = read_hgh_log(log.as_bytes(), Some(origin)).unwrap();
+        assert_eq!(database.static_cuas_location.lat, 40.);
+        let database = read_hgh_log(
+            include_str!("../testdata/hgh_v1.log").as_bytes(),
+            Some(origin),
+        )
+        .unwrap();
+        assert_eq!(database.static_cuas_location.lat, 40.);
+    }
+}
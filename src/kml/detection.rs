@@ -1,170 +1,132 @@
-use courageous_format::{Arc, Detection, Location, Position3d};
-use quick_xml::{events::BytesText, Writer};
-use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use courageous_format::{Detection, Location, Position3d};
 
 use super::{
-    ext_data::write_detection_extended_data,
+    ext_data::detection_extended_data,
     geometry::{
-        create_arc_polygon, point_from_bearing_elevation_distance, ray_from_bearing,
-        ray_from_bearing_elevation,
+        point_from_bearing, point_from_bearing_elevation_distance, relative_location_geometry,
     },
-    uav_home_location::write_uav_home_location,
+    model::{AltitudeMode, Feature, Folder, Geometry, Placemark, Point, TimePrimitive},
+    uav_home_location::uav_home_location_placemark,
+};
+use crate::{
+    record::{check_record_position, record_time},
+    Error, RecordSet,
 };
 
-pub fn write_detection_set(
-    x: &mut Writer<impl std::io::Write>,
+pub fn detection_set_folder(
     set: &[Detection],
     static_cuas_origin: Position3d,
     cuas_range: f64,
-) -> Result<(), quick_xml::Error> {
-    x.create_element("Folder").write_inner_content(|x| {
-        x.create_element("name")
-            .write_text_content(BytesText::new("Detection Sets"))?;
-
-        for detection in set {
-            write_detection(x, detection, static_cuas_origin, cuas_range)?;
-        }
-
-        Ok(())
-    })?;
-
-    Ok(())
+    angle_points: bool,
+) -> Result<Folder, Error> {
+    Ok(Folder {
+        name: "Detection Sets".to_owned(),
+        description: None,
+        features: set
+            .iter()
+            .enumerate()
+            .map(|(idx, detection)| {
+                detection_folder(
+                    detection,
+                    RecordSet::Detection(idx),
+                    static_cuas_origin,
+                    cuas_range,
+                    angle_points,
+                )
+                .map(Feature::Folder)
+            })
+            .collect::<Result<_, _>>()?,
+    })
 }
 
-pub fn write_detection(
-    x: &mut Writer<impl std::io::Write>,
+pub fn detection_folder(
     detection: &Detection,
+    set: RecordSet,
     static_cuas_origin: Position3d,
     cuas_range: f64,
-) -> Result<(), quick_xml::Error> {
-    x.create_element("Folder").write_inner_content(|x| {
-        x.create_element("name").write_text_content(BytesText::new(
-            detection.name.as_deref().unwrap_or("Unnamed detection set"),
-        ))?;
-        x.create_element("description")
-            .write_text_content(BytesText::new(&format!(
-                "UAV unique ID: {}",
-                detection
-                    .uas_id
-                    .map(|x| x.to_string())
-                    .unwrap_or_else(|| "None".to_owned())
-            )))?;
-        if let Some(uav_home_location) = detection.uav_home_location {
-            write_uav_home_location(x, uav_home_location)?;
-        };
-
-        for record in detection
-            .records
-            .iter()
-            .filter(|record| record.location.is_some())
-        {
-            x.create_element("Placemark").write_inner_content(|x| {
-                x.create_element("name")
-                    .write_text_content(BytesText::new(&format!("{}", record.record_number)))?;
-                let time_str =
-                    OffsetDateTime::from_unix_timestamp_nanos(record.time as i128 * 1_000_000)
-                        .unwrap()
-                        .format(&Rfc3339)
-                        .unwrap();
-                write_detection_extended_data(x, record)?;
+    angle_points: bool,
+) -> Result<Folder, Error> {
+    let mut folder = Folder {
+        name: detection
+            .name
+            .as_deref()
+            .unwrap_or("Unnamed detection set")
+            .to_owned(),
+        description: Some(format!(
+            "UAV unique ID: {}",
+            detection
+                .uas_id
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| "None".to_owned())
+        )),
+        features: Vec::new(),
+    };
+    if let Some(uav_home_location) = detection.uav_home_location {
+        folder
+            .features
+            .push(Feature::Placemark(uav_home_location_placemark(
+                uav_home_location,
+            )));
+    };
 
-                x.create_element("styleUrl")
-                    .write_text_content(BytesText::new("origin_style"))?;
-                x.create_element("TimeStamp").write_inner_content(|x| {
-                    x.create_element("when")
-                        .write_text_content(BytesText::new(&time_str))?;
-
-                    Ok(())
-                })?;
-
-                if let Some(location) = &record.location {
-                    match location {
-                        Location::Position3d(pos) => {
-                            x.create_element("Point").write_inner_content(|x| {
-                                x.create_element("extrude")
-                                    .write_text_content(BytesText::new("false"))?;
-                                x.create_element("altitudeMode")
-                                    .write_text_content(BytesText::new("absolute"))?;
-                                x.create_element("coordinates").write_text_content(
-                                    BytesText::new(&format!(
-                                        "{},{},{}",
-                                        pos.lon, pos.lat, pos.height
-                                    )),
-                                )?;
-
-                                Ok(())
-                            })?;
-                        }
-                        Location::Position2d(pos) => {
-                            x.create_element("Point").write_inner_content(|x| {
-                                x.create_element("extrude")
-                                    .write_text_content(BytesText::new("false"))?;
-                                x.create_element("altitudeMode")
-                                    .write_text_content(BytesText::new("clampToGround"))?;
-                                x.create_element("coordinates").write_text_content(
-                                    BytesText::new(&format!("{},{},0", pos.lon, pos.lat)),
-                                )?;
-
-                                Ok(())
-                            })?;
-                        }
-                        Location::BearingElevationDistance {
-                            bearing,
-                            elevation,
-                            distance,
-                        } => {
-                            point_from_bearing_elevation_distance(
-                                x,
-                                record.cuas_location.unwrap_or(static_cuas_origin),
-                                *bearing,
-                                *elevation,
-                                *distance,
-                            )?;
-                        }
-                        Location::BearingElevation { bearing, elevation } => {
-                            ray_from_bearing_elevation(
-                                x,
-                                record.cuas_location.unwrap_or(static_cuas_origin),
-                                *bearing,
-                                *elevation,
-                                cuas_range,
-                            )?;
-                        }
-                        Location::Bearing { bearing } => {
-                            ray_from_bearing(
-                                x,
-                                record.cuas_location.unwrap_or(static_cuas_origin),
-                                *bearing,
-                                cuas_range,
-                            )?;
-                        }
-                        Location::Quad { quad } => {
-                            let (bearing_from, bearing_to) = match quad {
-                                courageous_format::Quad::North => (45., -45.),
-                                courageous_format::Quad::East => (135., 45.),
-                                courageous_format::Quad::South => (225., 135.),
-                                courageous_format::Quad::West => (315., 225.),
-                            };
-                            create_arc_polygon(
-                                x,
-                                bearing_from,
-                                bearing_to,
-                                static_cuas_origin,
-                                cuas_range,
-                            )?;
-                        }
-                        Location::Arc(Arc { from, to }) => {
-                            create_arc_polygon(x, *from, *to, static_cuas_origin, cuas_range)?;
-                        }
-                    }
-                }
-
-                Ok(())
-            })?;
-        }
+    for record in detection.records.iter() {
+        let Some(location) = record.location else {
+            continue;
+        };
+        let geometry = match location {
+            Location::Position3d(pos) => Geometry::Point(Point {
+                extrude: false,
+                altitude_mode: AltitudeMode::Absolute,
+                coordinates: check_record_position(pos, set, record.record_number)?,
+            }),
+            Location::Position2d(pos) => Geometry::Point(Point {
+                extrude: false,
+                altitude_mode: AltitudeMode::ClampToGround,
+                coordinates: check_record_position(
+                    Position3d {
+                        lat: pos.lat,
+                        lon: pos.lon,
+                        height: 0.,
+                    },
+                    set,
+                    record.record_number,
+                )?,
+            }),
+            Location::Bearing { bearing } if angle_points => point_from_bearing(
+                record.cuas_location.unwrap_or(static_cuas_origin),
+                bearing,
+                cuas_range,
+            ),
+            Location::BearingElevation { bearing, elevation } if angle_points => {
+                point_from_bearing_elevation_distance(
+                    record.cuas_location.unwrap_or(static_cuas_origin),
+                    bearing,
+                    elevation,
+                    cuas_range,
+                )
+            }
+            location => relative_location_geometry(
+                location,
+                record.cuas_location.unwrap_or(static_cuas_origin),
+                static_cuas_origin,
+                cuas_range,
+            )
+            .expect("positions are handled above"),
+        };
 
-        Ok(())
-    })?;
+        folder.features.push(Feature::Placemark(Placemark {
+            name: Some(format!("{}", record.record_number)),
+            style_url: Some("origin_style".to_owned()),
+            time: Some(TimePrimitive::TimeStamp(record_time(
+                record.time,
+                set,
+                record.record_number,
+            )?)),
+            extended_data: Some(detection_extended_data(record)),
+            geometry: Some(geometry),
+            ..Default::default()
+        }));
+    }
 
-    Ok(())
+    Ok(folder)
 }
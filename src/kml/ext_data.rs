@@ -1,336 +1,139 @@
-use std::io::Write;
+use courageous_format::{DetectionRecord, TrackingRecord, Velocity};
 
-use courageous_format::{DetectionRecord, TrackingRecord};
-use quick_xml::{
-    events::{BytesCData, BytesText, Event},
-    Writer,
-};
+use super::model::{ExtendedData, Schema, SimpleArrayData, SimpleData, SimpleField};
+use crate::record::classification_str;
 
-pub fn write_schema(x: &mut Writer<impl Write>) -> Result<(), quick_xml::Error> {
-    x.create_element("Schema")
-        .with_attribute(("name", "schema_name"))
-        .with_attribute(("id", "schema"))
-        .write_inner_content(|x| {
-            x.create_element("SimpleField")
-                .with_attribute(("name", "record_number"))
-                .with_attribute(("type", "uint"))
-                .write_inner_content(|x| {
-                    x.create_element("displayName").write_inner_content(|x| {
-                        x.write_event(Event::CData(BytesCData::new("<b>Record Number</b>")))
-                    })?;
-                    Ok(())
-                })?;
-            x.create_element("SimpleField")
-                .with_attribute(("name", "classification"))
-                .with_attribute(("type", "string"))
-                .write_inner_content(|x| {
-                    x.create_element("displayName").write_inner_content(|x| {
-                        x.write_event(Event::CData(BytesCData::new("<b>Classification</b>")))
-                    })?;
-                    Ok(())
-                })?;
-            x.create_element("SimpleField")
-                .with_attribute(("name", "alarm"))
-                .with_attribute(("type", "bool"))
-                .write_inner_content(|x| {
-                    x.create_element("displayName").write_inner_content(|x| {
-                        x.write_event(Event::CData(BytesCData::new("<b>Alarm</b>")))
-                    })?;
-                    Ok(())
-                })?;
-            x.create_element("SimpleField")
-                .with_attribute(("name", "alarm_certainty"))
-                .with_attribute(("type", "float"))
-                .write_inner_content(|x| {
-                    x.create_element("displayName").write_inner_content(|x| {
-                        x.write_event(Event::CData(BytesCData::new("<b>Alarm Certainty</b>")))
-                    })?;
-                    Ok(())
-                })?;
-            x.create_element("SimpleField")
-                .with_attribute(("name", "identification"))
-                .with_attribute(("type", "string"))
-                .write_inner_content(|x| {
-                    x.create_element("displayName").write_inner_content(|x| {
-                        x.write_event(Event::CData(BytesCData::new("<b>Identification</b>")))
-                    })?;
-                    Ok(())
-                })?;
-            x.create_element("SimpleField")
-                .with_attribute(("name", "velocity"))
-                .with_attribute(("type", "string"))
-                .write_inner_content(|x| {
-                    x.create_element("displayName").write_inner_content(|x| {
-                        x.write_event(Event::CData(BytesCData::new("<b>Velocity</b>")))
-                    })?;
-                    Ok(())
-                })?;
-            Ok(())
-        })?;
-    Ok(())
+const SCHEMA_URL: &str = "#schema";
+
+pub fn schema() -> Schema {
+    let field = |name: &str, field_type: &str, display_name: &str| SimpleField {
+        name: name.to_owned(),
+        field_type: field_type.to_owned(),
+        display_name: format!("<b>{}</b>", display_name),
+    };
+    Schema {
+        id: "schema".to_owned(),
+        name: "schema_name".to_owned(),
+        fields: vec![
+            field("record_number", "uint", "Record Number"),
+            field("classification", "string", "Classification"),
+            field("alarm", "bool", "Alarm"),
+            field("alarm_certainty", "float", "Alarm Certainty"),
+            field("identification", "string", "Identification"),
+            field("velocity", "string", "Velocity"),
+        ],
+    }
 }
 
-pub fn write_detection_extended_data(
-    x: &mut Writer<impl Write>,
-    record: &DetectionRecord,
-) -> Result<(), quick_xml::Error> {
-    x.create_element("ExtendedData").write_inner_content(|x| {
-        x.create_element("SchemaData")
-            .with_attribute(("schemaUrl", "#schema"))
-            .write_inner_content(|x| {
-                x.create_element("SimpleData")
-                    .with_attribute(("name", "record_number"))
-                    .write_text_content(BytesText::new(&format!("{}", record.record_number)))?;
-                x.create_element("SimpleData")
-                    .with_attribute(("name", "classification"))
-                    .write_text_content(BytesText::new(match record.classification {
-                        courageous_format::Classification::Gcs => "GCS",
-                        courageous_format::Classification::Other => "Other",
-                        courageous_format::Classification::Uav => "UAV",
-                        courageous_format::Classification::Unknown => "Unknown",
-                    }))?;
-                let alarm = record.alarm.map_or(false, |a| a.active);
-                x.create_element("SimpleData")
-                    .with_attribute(("name", "alarm"))
-                    .write_text_content(BytesText::new(if alarm { "On" } else { "Off" }))?;
-                let certainty = record.alarm.map_or(0., |a| a.certainty);
-                x.create_element("SimpleData")
-                    .with_attribute(("name", "alarm_certainty"))
-                    .write_text_content(BytesText::new(&format!("{:.0}", certainty * 100.)))?;
-                x.create_element("SimpleData")
-                    .with_attribute(("name", "identification"))
-                    .write_text_content(BytesText::new(
-                        record.identification.as_deref().unwrap_or("<i>empty</i>"),
-                    ))?;
-                x.create_element("SimpleData")
-                    .with_attribute(("name", "velocity"))
-                    .write_text_content(BytesText::new(
-                        &record
-                            .velocity
-                            .map(|v| {
-                                let ew = if v.east.is_sign_positive() {
-                                    "east"
-                                } else {
-                                    "west"
-                                };
-                                let ns = if v.north.is_sign_positive() {
-                                    "north"
-                                } else {
-                                    "south"
-                                };
-                                let ud = if v.up.is_sign_positive() {
-                                    "up"
-                                } else {
-                                    "down"
-                                };
+pub fn detection_extended_data(record: &DetectionRecord) -> ExtendedData {
+    let alarm = record.alarm.is_some_and(|a| a.active);
+    let certainty = record.alarm.map_or(0., |a| a.certainty);
+    record_extended_data([
+        record.record_number.to_string(),
+        classification_str(record.classification).to_owned(),
+        alarm_str(alarm).to_owned(),
+        certainty_str(certainty),
+        identification_str(record.identification.as_deref()).to_owned(),
+        velocity_str(record.velocity),
+    ])
+}
 
-                                format!(
-                                    "{} m/s {}, {} m/s {}, {} m/s {}",
-                                    v.east.abs(),
-                                    ew,
-                                    v.north.abs(),
-                                    ns,
-                                    v.up.abs(),
-                                    ud,
-                                )
-                            })
-                            .unwrap_or("<i>not given</i>".to_owned()),
-                    ))?;
-                Ok(())
-            })?;
+pub fn tracking_extended_data(record: &TrackingRecord) -> ExtendedData {
+    record_extended_data([
+        record.record_number.to_string(),
+        classification_str(record.classification).to_owned(),
+        alarm_str(record.alarm.active).to_owned(),
+        certainty_str(record.alarm.certainty),
+        identification_str(record.identification.as_deref()).to_owned(),
+        velocity_str(record.velocity),
+    ])
+}
 
-        Ok(())
-    })?;
-    Ok(())
+pub fn gxtrack_extended_data(records: &[&TrackingRecord]) -> ExtendedData {
+    let array = |name: &str, value: fn(&TrackingRecord) -> String| SimpleArrayData {
+        name: name.to_owned(),
+        values: records.iter().map(|record| value(record)).collect(),
+    };
+    ExtendedData {
+        schema_url: SCHEMA_URL.to_owned(),
+        simple_data: Vec::new(),
+        simple_array_data: vec![
+            array("record_number", |r| r.record_number.to_string()),
+            array("classification", |r| {
+                classification_str(r.classification).to_owned()
+            }),
+            array("alarm", |r| alarm_str(r.alarm.active).to_owned()),
+            array("alarm_certainty", |r| certainty_str(r.alarm.certainty)),
+            array("identification", |r| {
+                identification_str(r.identification.as_deref()).to_owned()
+            }),
+            array("velocity", |r| velocity_str(r.velocity)),
+        ],
+    }
 }
 
-pub fn write_tracking_extended_data(
-    x: &mut Writer<impl Write>,
-    record: &TrackingRecord,
-) -> Result<(), quick_xml::Error> {
-    x.create_element("ExtendedData").write_inner_content(|x| {
-        x.create_element("SchemaData")
-            .with_attribute(("schemaUrl", "#schema"))
-            .write_inner_content(|x| {
-                x.create_element("SimpleData")
-                    .with_attribute(("name", "record_number"))
-                    .write_text_content(BytesText::new(&format!("{}", record.record_number)))?;
-                x.create_element("SimpleData")
-                    .with_attribute(("name", "classification"))
-                    .write_text_content(BytesText::new(match record.classification {
-                        courageous_format::Classification::Gcs => "GCS",
-                        courageous_format::Classification::Other => "Other",
-                        courageous_format::Classification::Uav => "UAV",
-                        courageous_format::Classification::Unknown => "Unknown",
-                    }))?;
-                let alarm = record.alarm.active;
-                x.create_element("SimpleData")
-                    .with_attribute(("name", "alarm"))
-                    .write_text_content(BytesText::new(if alarm { "On" } else { "Off" }))?;
-                let certainty = record.alarm.certainty;
-                x.create_element("SimpleData")
-                    .with_attribute(("name", "alarm_certainty"))
-                    .write_text_content(BytesText::new(&format!("{:.0}", certainty * 100.)))?;
-                x.create_element("SimpleData")
-                    .with_attribute(("name", "identification"))
-                    .write_text_content(BytesText::new(
-                        record.identification.as_deref().unwrap_or("<i>empty</i>"),
-                    ))?;
-                x.create_element("SimpleData")
-                    .with_attribute(("name", "velocity"))
-                    .write_text_content(BytesText::new(
-                        &record
-                            .velocity
-                            .map(|v| {
-                                let ew = if v.east.is_sign_positive() {
-                                    "east"
-                                } else {
-                                    "west"
-                                };
-                                let ns = if v.north.is_sign_positive() {
-                                    "north"
-                                } else {
-                                    "south"
-                                };
-                                let ud = if v.up.is_sign_positive() {
-                                    "up"
-                                } else {
-                                    "down"
-                                };
+/// Creates the extended data of a single record, given its values in the same order as the fields
+/// of [`schema`].
+fn record_extended_data(values: [String; 6]) -> ExtendedData {
+    ExtendedData {
+        schema_url: SCHEMA_URL.to_owned(),
+        simple_data: schema()
+            .fields
+            .into_iter()
+            .zip(values)
+            .map(|(field, value)| SimpleData {
+                name: field.name,
+                value,
+            })
+            .collect(),
+        simple_array_data: Vec::new(),
+    }
+}
 
-                                format!(
-                                    "{} m/s {}, {} m/s {}, {} m/s {}",
-                                    v.east.abs(),
-                                    ew,
-                                    v.north.abs(),
-                                    ns,
-                                    v.up.abs(),
-                                    ud,
-                                )
-                            })
-                            .unwrap_or("<i>not given</i>".to_owned()),
-                    ))?;
-                Ok(())
-            })?;
+fn alarm_str(alarm: bool) -> &'static str {
+    if alarm {
+        "On"
+    } else {
+        "Off"
+    }
+}
 
-        Ok(())
-    })?;
-    Ok(())
+fn certainty_str(certainty: f64) -> String {
+    format!("{:.0}", certainty * 100.)
 }
 
-pub fn write_gxtrack_extended_data(
-    x: &mut Writer<impl Write>,
-    records: &[&TrackingRecord],
-) -> Result<(), quick_xml::Error> {
-    x.create_element("ExtendedData").write_inner_content(|x| {
-        x.create_element("SchemaData")
-            .with_attribute(("schemaUrl", "#schema"))
-            .write_inner_content(|x| {
-                x.create_element("gx:SimpleArrayData")
-                    .with_attribute(("name", "record_number"))
-                    .write_inner_content(|x| {
-                        for record in records.iter() {
-                            x.create_element("gx:value")
-                                .write_text_content(BytesText::new(&format!(
-                                    "{}",
-                                    record.record_number
-                                )))?;
-                        }
-                        Ok(())
-                    })?;
-                x.create_element("gx:SimpleArrayData")
-                    .with_attribute(("name", "classification"))
-                    .write_inner_content(|x| {
-                        for record in records.iter() {
-                            x.create_element("gx:value")
-                                .write_text_content(BytesText::new(
-                                    match record.classification {
-                                        courageous_format::Classification::Gcs => "GCS",
-                                        courageous_format::Classification::Other => "Other",
-                                        courageous_format::Classification::Uav => "UAV",
-                                        courageous_format::Classification::Unknown => "Unknown",
-                                    },
-                                ))?;
-                        }
-                        Ok(())
-                    })?;
-                x.create_element("gx:SimpleArrayData")
-                    .with_attribute(("name", "alarm"))
-                    .write_inner_content(|x| {
-                        for record in records.iter() {
-                            let alarm = record.alarm.active;
-                            x.create_element("gx:value")
-                                .write_text_content(BytesText::new(if alarm {
-                                    "On"
-                                } else {
-                                    "Off"
-                                }))?;
-                        }
-                        Ok(())
-                    })?;
-                x.create_element("gx:SimpleArrayData")
-                    .with_attribute(("name", "alarm_certainty"))
-                    .write_inner_content(|x| {
-                        for record in records.iter() {
-                            let certainty = record.alarm.certainty;
-                            x.create_element("gx:value")
-                                .write_text_content(BytesText::new(&format!(
-                                    "{:.0}",
-                                    certainty * 100.
-                                )))?;
-                        }
-                        Ok(())
-                    })?;
-                x.create_element("gx:SimpleArrayData")
-                    .with_attribute(("name", "identification"))
-                    .write_inner_content(|x| {
-                        for record in records.iter() {
-                            x.create_element("gx:value")
-                                .write_text_content(BytesText::new(
-                                    record.identification.as_deref().unwrap_or("<i>empty</i>"),
-                                ))?;
-                        }
-                        Ok(())
-                    })?;
-                x.create_element("gx:SimpleArrayData")
-                    .with_attribute(("name", "velocity"))
-                    .write_inner_content(|x| {
-                        for record in records.iter() {
-                            x.create_element("gx:value")
-                                .write_text_content(BytesText::new(
-                                    &record
-                                        .velocity
-                                        .map(|v| {
-                                            let ud = if v.up.is_sign_positive() {
-                                                "up"
-                                            } else {
-                                                "down"
-                                            };
-                                            let ew = if v.east.is_sign_positive() {
-                                                "east"
-                                            } else {
-                                                "west"
-                                            };
-                                            let ns = if v.north.is_sign_positive() {
-                                                "north"
-                                            } else {
-                                                "south"
-                                            };
+fn identification_str(identification: Option<&str>) -> &str {
+    identification.unwrap_or("<i>empty</i>")
+}
 
-                                            format!(
-                                                "{} m/s {}, {} m/s {}, {} m/s {}",
-                                                v.up, ud, v.east, ew, v.north, ns
-                                            )
-                                        })
-                                        .unwrap_or("<i>not given</i>".to_owned()),
-                                ))?;
-                        }
-                        Ok(())
-                    })?;
-                Ok(())
-            })?;
+fn velocity_str(velocity: Option<Velocity>) -> String {
+    velocity
+        .map(|v| {
+            let ew = if v.east.is_sign_positive() {
+                "east"
+            } else {
+                "west"
+            };
+            let ns = if v.north.is_sign_positive() {
+                "north"
+            } else {
+                "south"
+            };
+            let ud = if v.up.is_sign_positive() {
+                "up"
+            } else {
+                "down"
+            };
 
-        Ok(())
-    })?;
-    Ok(())
+            format!(
+                "{} m/s {}, {} m/s {}, {} m/s {}",
+                v.east.abs(),
+                ew,
+                v.north.abs(),
+                ns,
+                v.up.abs(),
+                ud,
+            )
+        })
+        .unwrap_or("<i>not given</i>".to_owned())
 }
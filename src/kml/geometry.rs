@@ -1,151 +1,127 @@
-use std::io::Write;
+use courageous_format::{Arc, Location, Position3d, Quad};
+use map_3d::{aer2geodetic, geodetic2aer, Ellipsoid};
 
-use courageous_format::Position3d;
-use map_3d::{aer2geodetic, Ellipsoid};
-use quick_xml::{events::BytesText, Writer};
+use super::model::{AltitudeMode, Geometry, LineString, Point, Polygon};
 
-pub fn ray_from_bearing(
-    x: &mut Writer<impl Write>,
-    cuas_origin: Position3d,
-    bearing: f64,
-    cuas_range: f64,
-) -> Result<(), quick_xml::Error> {
+pub fn ray_from_bearing(cuas_origin: Position3d, bearing: f64, cuas_range: f64) -> Geometry {
     let target = distance_from_position(cuas_origin, bearing, 0., cuas_range);
-    x.create_element("LineString").write_inner_content(|x| {
-        x.create_element("extrude")
-            .write_text_content(BytesText::new("0"))?;
-        x.create_element("tessellate")
-            .write_text_content(BytesText::new("0"))?;
-        x.create_element("altitudeMode")
-            .write_text_content(BytesText::new("clampToGround"))?;
-        x.create_element("coordinates")
-            .write_text_content(BytesText::new(&format!(
-                "{},{},{} {},{},{}",
-                cuas_origin.lon,
-                cuas_origin.lat,
-                cuas_origin.height,
-                target.lon,
-                target.lat,
-                target.height
-            )))?;
-
-        Ok(())
-    })?;
-    Ok(())
+    Geometry::LineString(LineString {
+        extrude: false,
+        tessellate: false,
+        altitude_mode: AltitudeMode::ClampToGround,
+        coordinates: vec![cuas_origin, target],
+    })
 }
 
 pub fn ray_from_bearing_elevation(
-    x: &mut Writer<impl Write>,
     cuas_origin: Position3d,
     bearing: f64,
     elevation: f64,
     cuas_range: f64,
-) -> Result<(), quick_xml::Error> {
+) -> Geometry {
     let target = distance_from_position(cuas_origin, bearing, elevation, cuas_range);
-    x.create_element("LineString").write_inner_content(|x| {
-        x.create_element("extrude")
-            .write_text_content(BytesText::new("0"))?;
-        x.create_element("tessellate")
-            .write_text_content(BytesText::new("0"))?;
-        x.create_element("altitudeMode")
-            .write_text_content(BytesText::new("absolute"))?;
-        x.create_element("coordinates")
-            .write_text_content(BytesText::new(&format!(
-                "{},{},{} {},{},{}",
-                cuas_origin.lon,
-                cuas_origin.lat,
-                cuas_origin.height,
-                target.lon,
-                target.lat,
-                target.height
-            )))?;
+    Geometry::LineString(LineString {
+        extrude: false,
+        tessellate: false,
+        altitude_mode: AltitudeMode::Absolute,
+        coordinates: vec![cuas_origin, target],
+    })
+}
 
-        Ok(())
-    })?;
-    Ok(())
+pub fn point_from_bearing(cuas_origin: Position3d, bearing: f64, distance: f64) -> Geometry {
+    let pos = distance_from_position(cuas_origin, bearing, 0., distance);
+    Geometry::Point(Point {
+        extrude: false,
+        altitude_mode: AltitudeMode::ClampToGround,
+        coordinates: pos,
+    })
 }
 
 pub fn point_from_bearing_elevation_distance(
-    x: &mut Writer<impl Write>,
     cuas_origin: Position3d,
     bearing: f64,
     elevation: f64,
     distance: f64,
-) -> Result<(), quick_xml::Error> {
+) -> Geometry {
     let pos = distance_from_position(cuas_origin, bearing, elevation, distance);
-    x.create_element("Point").write_inner_content(|x| {
-        x.create_element("extrude")
-            .write_text_content(BytesText::new("0"))?;
-        x.create_element("altitudeMode")
-            .write_text_content(BytesText::new("absolute"))?;
-        x.create_element("coordinates")
-            .write_text_content(BytesText::new(&format!(
-                "{},{},{}",
-                pos.lon, pos.lat, pos.height
-            )))?;
-
-        Ok(())
-    })?;
-    Ok(())
+    Geometry::Point(Point {
+        extrude: false,
+        altitude_mode: AltitudeMode::Absolute,
+        coordinates: pos,
+    })
 }
 
 pub fn create_arc_polygon(
-    x: &mut Writer<impl Write>,
     bearing_from: f64,
     bearing_to: f64,
     static_cuas_origin: Position3d,
     cuas_range: f64,
-) -> Result<(), quick_xml::Error> {
-    x.create_element("Polygon").write_inner_content(|x| {
-        x.create_element("extrude")
-            .write_text_content(BytesText::new("0"))?;
-        x.create_element("tessellate")
-            .write_text_content(BytesText::new("1"))?;
-        x.create_element("altitudeMode")
-            .write_text_content(BytesText::new("clampToGround"))?;
-        x.create_element("outerBoundaryIs")
-            .write_inner_content(|x| {
-                x.create_element("LinearRing").write_inner_content(|x| {
-                    const ARC_POINT_COUNT: usize = 64;
+) -> Geometry {
+    Geometry::Polygon(Polygon {
+        extrude: false,
+        tessellate: true,
+        altitude_mode: AltitudeMode::ClampToGround,
+        outer_boundary: arc_sector(bearing_from, bearing_to, static_cuas_origin, cuas_range),
+    })
+}
 
-                    let arc_points = (0..ARC_POINT_COUNT).map(|idx| {
-                        let angle_deg = bearing_from
-                            + (bearing_to - bearing_from) * (idx as f64 / ARC_POINT_COUNT as f64);
-                        (angle_deg.to_radians(), 0., cuas_range)
-                    });
+/// Returns the outline of the circular sector centered at `origin` going from `bearing_from` to
+/// `bearing_to`, starting with `origin` itself. The outline is not closed.
+pub fn arc_sector(
+    bearing_from: f64,
+    bearing_to: f64,
+    origin: Position3d,
+    range: f64,
+) -> Vec<Position3d> {
+    const ARC_POINT_COUNT: usize = 64;
 
-                    let points = std::iter::once((
-                        static_cuas_origin.lat,
-                        static_cuas_origin.lon,
-                        static_cuas_origin.height,
-                    ))
-                    .chain(arc_points.map(|(az, el, slant_range)| {
-                        let (lat, lon, alt) = aer2geodetic(
-                            az,
-                            el,
-                            slant_range,
-                            static_cuas_origin.lat.to_radians(),
-                            static_cuas_origin.lon.to_radians(),
-                            static_cuas_origin.height,
-                            Ellipsoid::WGS84,
-                        );
-                        (lat.to_degrees(), lon.to_degrees(), alt)
-                    }));
+    let arc_points = (0..ARC_POINT_COUNT).map(|idx| {
+        let angle_deg =
+            bearing_from + (bearing_to - bearing_from) * (idx as f64 / ARC_POINT_COUNT as f64);
+        distance_from_position(origin, angle_deg, 0., range)
+    });
 
-                    let coordinates_str = points
-                        .map(|(lat, lon, _height)| format!("{},{} ", lon, lat))
-                        .collect::<String>();
+    std::iter::once(origin).chain(arc_points).collect()
+}
 
-                    x.create_element("coordinates")
-                        .write_text_content(BytesText::new(&coordinates_str))?;
-                    Ok(())
-                })?;
-                Ok(())
-            })?;
+/// Returns the bearings (In degrees) delimiting a quadrant, in the order they must be given to
+/// [`create_arc_polygon`].
+pub fn quad_bearings(quad: Quad) -> (f64, f64) {
+    match quad {
+        Quad::North => (45., -45.),
+        Quad::East => (135., 45.),
+        Quad::South => (225., 135.),
+        Quad::West => (315., 225.),
+    }
+}
 
-        Ok(())
-    })?;
-    Ok(())
+/// Creates the geometry of an angle-based or distance-based location, i.e. any location other than
+/// `Position2d` or `Position3d`.
+pub fn relative_location_geometry(
+    location: Location,
+    cuas_origin: Position3d,
+    static_cuas_origin: Position3d,
+    cuas_range: f64,
+) -> Option<Geometry> {
+    Some(match location {
+        Location::BearingElevationDistance {
+            bearing,
+            elevation,
+            distance,
+        } => point_from_bearing_elevation_distance(cuas_origin, bearing, elevation, distance),
+        Location::BearingElevation { bearing, elevation } => {
+            ray_from_bearing_elevation(cuas_origin, bearing, elevation, cuas_range)
+        }
+        Location::Bearing { bearing } => ray_from_bearing(cuas_origin, bearing, cuas_range),
+        Location::Quad { quad } => {
+            let (bearing_from, bearing_to) = quad_bearings(quad);
+            create_arc_polygon(bearing_from, bearing_to, static_cuas_origin, cuas_range)
+        }
+        Location::Arc(Arc { from, to }) => {
+            create_arc_polygon(from, to, static_cuas_origin, cuas_range)
+        }
+        Location::Position2d(_) | Location::Position3d(_) => return None,
+    })
 }
 
 pub fn distance_from_position(
@@ -169,3 +145,22 @@ pub fn distance_from_position(
         height,
     }
 }
+
+/// The inverse of [`distance_from_position`]: Returns the azimuth and elevation (In degrees) and
+/// the distance from `origin` to `target`.
+pub fn bearing_elevation_distance(origin: Position3d, target: Position3d) -> (f64, f64, f64) {
+    let (azimuth, elevation, distance) = geodetic2aer(
+        target.lat.to_radians(),
+        target.lon.to_radians(),
+        target.height,
+        origin.lat.to_radians(),
+        origin.lon.to_radians(),
+        origin.height,
+        Ellipsoid::WGS84,
+    );
+    (
+        azimuth.to_degrees().rem_euclid(360.),
+        elevation.to_degrees(),
+        distance,
+    )
+}
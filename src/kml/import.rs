@@ -0,0 +1,762 @@
+use std::io::BufRead;
+
+use courageous_format::{
+    Alarm, Arc, Classification, Detection, DetectionRecord, Location, Position2d, Position3d, Quad,
+    Track, TrackingRecord, Velocity,
+};
+use time::OffsetDateTime;
+
+use super::{
+    geometry::{bearing_elevation_distance, quad_bearings},
+    model::{AltitudeMode, Document, ExtendedData, Feature, Folder, Geometry, TimePrimitive},
+    parse::{invalid, parse_kml, parse_value},
+};
+use crate::{Database, Error};
+
+/// Reads a KML document generated by this crate back into a [`Database`].
+///
+/// See [`database_from_kml`] for the limitations of the conversion.
+pub fn read_kml(reader: impl BufRead) -> Result<Database, Error> {
+    database_from_kml(&parse_kml(reader)?)
+}
+
+/// Recovers the [`Database`] a KML [`Document`] was built from by [`build_kml`](super::build_kml).
+///
+/// Absolute positions, times and the data shown on each record are recovered as they were, but KML
+/// does not store everything a COURAGEOUS document does:
+/// - Alarm certainties are rounded to whole percentages.
+/// - Bearings, elevations and distances are calculated back from the geometry that represented
+///   them, and so are subject to rounding errors. Records of detection sets with a
+///   `BearingElevationDistance` location are read as `Position3d` ones.
+/// - Tracks are split into one folder per classification. Consecutive folders of the same track
+///   are joined back, so two consecutive tracks with the same name and UAS ID whose records have
+///   different classifications are read as a single one.
+/// - Versions 0.4.2 and earlier did not store the UAS ID of named tracks, so the index of the track is
+///   used instead for them.
+/// - Inactive alarms with 0% certainty of detection records are read as no alarm, since both are
+///   written the same way.
+/// - Records of each track are sorted by time, then by record number.
+pub fn database_from_kml(document: &Document) -> Result<Database, Error> {
+    let folder = |name: &str| {
+        document.features.iter().find_map(|feature| match feature {
+            Feature::Folder(folder) if folder.name == name => Some(folder),
+            _ => None,
+        })
+    };
+    let static_cuas_location = document
+        .features
+        .iter()
+        .find_map(|feature| match feature {
+            Feature::Placemark(placemark)
+                if placemark.style_url.as_deref() == Some("cuas_style") =>
+            {
+                match placemark.geometry {
+                    Some(Geometry::Point(point)) => Some(point.coordinates),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .ok_or_else(|| invalid("document has no static CUAS location"))?;
+
+    Ok(Database {
+        detection: folder("Detection Sets").map_or(Ok(Vec::new()), |folder| {
+            subfolders(folder)
+                .map(|folder| detection_from_folder(folder, static_cuas_location))
+                .collect()
+        })?,
+        tracks: folder("Tracks").map_or(Ok(Vec::new()), |folder| {
+            tracks_from_folder(folder, static_cuas_location)
+        })?,
+        static_cuas_location,
+    })
+}
+
+fn subfolders(folder: &Folder) -> impl Iterator<Item = &Folder> {
+    folder.features.iter().filter_map(|feature| match feature {
+        Feature::Folder(folder) => Some(folder),
+        Feature::Placemark(_) => None,
+    })
+}
+
+fn detection_from_folder(
+    folder: &Folder,
+    static_cuas_location: Position3d,
+) -> Result<Detection, Error> {
+    let mut detection = Detection {
+        uas_id: folder_uas_id(folder)?,
+        name: (folder.name != "Unnamed detection set").then(|| folder.name.clone()),
+        records: Vec::new(),
+        uav_home_location: None,
+    };
+
+    for feature in &folder.features {
+        let Feature::Placemark(placemark) = feature else {
+            continue;
+        };
+        let Some(geometry) = &placemark.geometry else {
+            continue;
+        };
+        if placemark.style_url.as_deref() == Some("uav_home_style") {
+            detection.uav_home_location = point_coordinates(geometry);
+            continue;
+        }
+
+        let data = RecordData::from_simple_data(required_extended_data(
+            placemark.extended_data.as_ref(),
+        )?)?;
+        let (location, cuas_location) = match geometry {
+            Geometry::Point(point) if point.altitude_mode == AltitudeMode::Absolute => {
+                (Location::Position3d(point.coordinates), None)
+            }
+            Geometry::Point(point) => (
+                Location::Position2d(Position2d {
+                    lat: point.coordinates.lat,
+                    lon: point.coordinates.lon,
+                }),
+                None,
+            ),
+            geometry => relative_location(geometry, static_cuas_location)?,
+        };
+        detection.records.push(DetectionRecord {
+            time: placemark_time(placemark.time)?,
+            record_number: data.record_number,
+            classification: data.classification,
+            alarm: (data.alarm.active || data.alarm.certainty != 0.).then_some(data.alarm),
+            location: Some(location),
+            identification: data.identification,
+            velocity: data.velocity,
+            cuas_location,
+        });
+    }
+
+    Ok(detection)
+}
+
+/// Groups the folders of each classification back into tracks.
+///
+/// Consecutive folders belong to the same track if they have the same base name and UAS ID, and
+/// their classifications follow the order used by [`classified_track_folders`]. Folders without a
+/// classification suffix, as written by versions before 0.4.2, are a whole track each; the
+/// classification of their records is read from their extended data either way.
+///
+/// [`classified_track_folders`]: super::tracking::classified_track_folders
+fn tracks_from_folder(
+    folder: &Folder,
+    static_cuas_location: Position3d,
+) -> Result<Vec<Track>, Error> {
+    let mut groups: Vec<TrackFolders> = Vec::new();
+    for folder in subfolders(folder) {
+        let uas_id = folder_uas_id(folder)?;
+        let classified = folder
+            .name
+            .rsplit_once(" (")
+            .and_then(|(base_name, classification)| {
+                let classification = parse_classification(classification.strip_suffix(')')?);
+                Some((base_name, classification_order(classification.ok()?)))
+            });
+        let Some((base_name, order)) = classified else {
+            groups.push(TrackFolders {
+                base_name: &folder.name,
+                uas_id,
+                last_order: None,
+                folders: vec![folder],
+            });
+            continue;
+        };
+        match groups.last_mut() {
+            Some(group)
+                if group.base_name == base_name
+                    && group.uas_id == uas_id
+                    && group
+                        .last_order
+                        .is_some_and(|last_order| last_order < order) =>
+            {
+                group.last_order = Some(order);
+                group.folders.push(folder);
+            }
+            _ => groups.push(TrackFolders {
+                base_name,
+                uas_id,
+                last_order: Some(order),
+                folders: vec![folder],
+            }),
+        }
+    }
+
+    groups
+        .into_iter()
+        .enumerate()
+        .map(|(idx, group)| track_from_folders(idx, group, static_cuas_location))
+        .collect()
+}
+
+/// The folders a single track was split into.
+struct TrackFolders<'a> {
+    base_name: &'a str,
+    uas_id: Option<u64>,
+    /// Order of the classification of the last folder, if it had a classification suffix.
+    last_order: Option<usize>,
+    folders: Vec<&'a Folder>,
+}
+
+fn track_from_folders(
+    idx: usize,
+    group: TrackFolders,
+    static_cuas_location: Position3d,
+) -> Result<Track, Error> {
+    let TrackFolders {
+        base_name,
+        uas_id,
+        folders,
+        ..
+    } = group;
+    let unnamed_uas_id = base_name
+        .strip_prefix("Unnamed track (UAS ID ")
+        .and_then(|uas_id| uas_id.strip_suffix(')'));
+    let mut track = Track {
+        uas_id: match (uas_id, unnamed_uas_id) {
+            (Some(uas_id), _) => uas_id,
+            (None, Some(uas_id)) => parse_value(uas_id, "UAS ID")?,
+            (None, None) => idx as u64,
+        },
+        name: unnamed_uas_id.is_none().then(|| base_name.to_owned()),
+        records: Vec::new(),
+        uav_home_location: None,
+    };
+
+    for placemark in folders
+        .iter()
+        .flat_map(|folder| &folder.features)
+        .filter_map(|feature| match feature {
+            Feature::Placemark(placemark) => Some(placemark),
+            Feature::Folder(_) => None,
+        })
+    {
+        let Some(geometry) = &placemark.geometry else {
+            continue;
+        };
+        if placemark.style_url.as_deref() == Some("uav_home_style") {
+            track.uav_home_location = point_coordinates(geometry);
+            continue;
+        }
+
+        if let Geometry::MultiTrack(multi_track) = geometry {
+            for gx_track in &multi_track.tracks {
+                let extended_data = required_extended_data(gx_track.extended_data.as_ref())?;
+                for (idx, (&when, &pos)) in gx_track.when.iter().zip(&gx_track.coords).enumerate() {
+                    let data = RecordData::from_simple_array_data(extended_data, idx)?;
+                    let location = if gx_track.altitude_mode == AltitudeMode::Absolute {
+                        Location::Position3d(pos)
+                    } else {
+                        Location::Position2d(Position2d {
+                            lat: pos.lat,
+                            lon: pos.lon,
+                        })
+                    };
+                    track
+                        .records
+                        .push(data.tracking_record(time_millis(when)?, location, None));
+                }
+            }
+            continue;
+        }
+
+        let data = RecordData::from_simple_data(required_extended_data(
+            placemark.extended_data.as_ref(),
+        )?)?;
+        let (location, cuas_location) = relative_location(geometry, static_cuas_location)?;
+        track.records.push(data.tracking_record(
+            placemark_time(placemark.time)?,
+            location,
+            cuas_location,
+        ));
+    }
+    track
+        .records
+        .sort_by_key(|record| (record.time, record.record_number));
+
+    Ok(track)
+}
+
+/// The values written into the `schema` extended data of a record.
+struct RecordData {
+    record_number: u64,
+    classification: Classification,
+    alarm: Alarm,
+    identification: Option<String>,
+    velocity: Option<Velocity>,
+}
+
+impl RecordData {
+    fn from_simple_data(data: &ExtendedData) -> Result<RecordData, Error> {
+        RecordData::from_values(|name| {
+            data.simple_data
+                .iter()
+                .find(|simple_data| simple_data.name == name)
+                .map(|simple_data| simple_data.value.as_str())
+        })
+    }
+
+    fn from_simple_array_data(data: &ExtendedData, idx: usize) -> Result<RecordData, Error> {
+        RecordData::from_values(|name| {
+            data.simple_array_data
+                .iter()
+                .find(|array_data| array_data.name == name)
+                .and_then(|array_data| array_data.values.get(idx))
+                .map(String::as_str)
+        })
+    }
+
+    fn from_values<'a>(value: impl Fn(&str) -> Option<&'a str>) -> Result<RecordData, Error> {
+        let required =
+            |name: &str| value(name).ok_or_else(|| invalid(format!("record has no {} data", name)));
+        Ok(RecordData {
+            record_number: parse_value(required("record_number")?, "record number")?,
+            classification: parse_classification(required("classification")?)?,
+            alarm: Alarm {
+                active: match required("alarm")? {
+                    "On" => true,
+                    "Off" => false,
+                    alarm => return Err(invalid(format!("invalid alarm {:?}", alarm))),
+                },
+                certainty: parse_value::<f64>(required("alarm_certainty")?, "alarm certainty")?
+                    / 100.,
+            },
+            identification: match required("identification")? {
+                "<i>empty</i>" => None,
+                identification => Some(identification.to_owned()),
+            },
+            velocity: parse_velocity(required("velocity")?)?,
+        })
+    }
+
+    fn tracking_record(
+        self,
+        time: u64,
+        location: Location,
+        cuas_location: Option<Position3d>,
+    ) -> TrackingRecord {
+        TrackingRecord {
+            time,
+            record_number: self.record_number,
+            classification: self.classification,
+            alarm: self.alarm,
+            location,
+            identification: self.identification,
+            velocity: self.velocity,
+            cuas_location,
+        }
+    }
+}
+
+/// Recovers an angle-based or distance-based location from the geometry created for it by
+/// [`relative_location_geometry`], along with the CUAS location it is relative to if it is not
+/// the static one.
+///
+/// [`relative_location_geometry`]: super::geometry::relative_location_geometry
+fn relative_location(
+    geometry: &Geometry,
+    static_cuas_location: Position3d,
+) -> Result<(Location, Option<Position3d>), Error> {
+    match geometry {
+        Geometry::Point(point) => {
+            let (bearing, elevation, distance) =
+                bearing_elevation_distance(static_cuas_location, point.coordinates);
+            Ok((
+                Location::BearingElevationDistance {
+                    bearing,
+                    elevation,
+                    distance,
+                },
+                None,
+            ))
+        }
+        Geometry::LineString(line) => {
+            let &[origin, target] = line.coordinates.as_slice() else {
+                return Err(invalid("rays must have exactly two coordinates"));
+            };
+            let (bearing, elevation, _) = bearing_elevation_distance(origin, target);
+            let location = if line.altitude_mode == AltitudeMode::ClampToGround {
+                Location::Bearing { bearing }
+            } else {
+                Location::BearingElevation { bearing, elevation }
+            };
+            Ok((location, (origin != static_cuas_location).then_some(origin)))
+        }
+        Geometry::Polygon(polygon) => {
+            // The outline starts with the CUAS location, followed by the points of the arc, the
+            // last one being one step before its end
+            let [origin, first, .., last] = polygon.outer_boundary.as_slice() else {
+                return Err(invalid("arcs must have at least three coordinates"));
+            };
+            let arc_steps = (polygon.outer_boundary.len() - 1) as f64;
+            let (from, _, _) = bearing_elevation_distance(*origin, *first);
+            let (last, _, _) = bearing_elevation_distance(*origin, *last);
+            let span = (last - from + 180.).rem_euclid(360.) - 180.;
+            let to = from + span * arc_steps / (arc_steps - 1.);
+
+            let quad = [Quad::North, Quad::East, Quad::South, Quad::West]
+                .into_iter()
+                .find(|&quad| {
+                    let (quad_from, quad_to) = quad_bearings(quad);
+                    angles_match(from, quad_from) && angles_match(to, quad_to)
+                });
+            let location = match quad {
+                Some(quad) => Location::Quad { quad },
+                None => Location::Arc(Arc { from, to }),
+            };
+            Ok((location, None))
+        }
+        Geometry::MultiTrack(_) => Err(invalid("unexpected gx:MultiTrack")),
+    }
+}
+
+fn angles_match(a: f64, b: f64) -> bool {
+    const TOLERANCE: f64 = 1e-3;
+    ((a - b + 180.).rem_euclid(360.) - 180.).abs() < TOLERANCE
+}
+
+/// Reads the UAS ID written into the description of detection set and track folders.
+fn folder_uas_id(folder: &Folder) -> Result<Option<u64>, Error> {
+    match folder
+        .description
+        .as_deref()
+        .and_then(|description| description.strip_prefix("UAV unique ID: "))
+    {
+        None | Some("None") => Ok(None),
+        Some(uas_id) => Ok(Some(parse_value(uas_id, "UAS ID")?)),
+    }
+}
+
+fn point_coordinates(geometry: &Geometry) -> Option<Position3d> {
+    match geometry {
+        Geometry::Point(point) => Some(point.coordinates),
+        _ => None,
+    }
+}
+
+fn required_extended_data(data: Option<&ExtendedData>) -> Result<&ExtendedData, Error> {
+    data.ok_or_else(|| invalid("record has no extended data"))
+}
+
+fn placemark_time(time: Option<TimePrimitive>) -> Result<u64, Error> {
+    match time {
+        Some(TimePrimitive::TimeStamp(when)) => time_millis(when),
+        _ => Err(invalid("record has no timestamp")),
+    }
+}
+
+/// The inverse of [`record_time`](crate::record::record_time).
+fn time_millis(time: OffsetDateTime) -> Result<u64, Error> {
+    u64::try_from(time.unix_timestamp_nanos() / 1_000_000)
+        .map_err(|_| invalid(format!("time {} is before the UNIX epoch", time)))
+}
+
+fn parse_classification(text: &str) -> Result<Classification, Error> {
+    match text {
+        "UAV" => Ok(Classification::Uav),
+        "GCS" => Ok(Classification::Gcs),
+        "Other" => Ok(Classification::Other),
+        "Unknown" => Ok(Classification::Unknown),
+        _ => Err(invalid(format!("invalid classification {:?}", text))),
+    }
+}
+
+fn classification_order(classification: Classification) -> usize {
+    match classification {
+        Classification::Uav => 0,
+        Classification::Gcs => 1,
+        Classification::Other => 2,
+        Classification::Unknown => 3,
+    }
+}
+
+/// Parses the velocity format of [`ext_data`](super::ext_data), e.g.
+/// `1 m/s east, 2 m/s south, 0 m/s up`.
+///
+/// Versions 0.4.2 and earlier wrote the velocities of `gx:Track`s in up, east, north order and with
+/// signed speeds instead, e.g. `-0 m/s down, 1 m/s east, -2 m/s south`. Each speed is matched to
+/// its axis and given its sign by its direction, so both formats are read the same way.
+fn parse_velocity(text: &str) -> Result<Option<Velocity>, Error> {
+    if text == "<i>not given</i>" {
+        return Ok(None);
+    }
+
+    let invalid_velocity = || invalid(format!("invalid velocity {:?}", text));
+    let (mut east, mut north, mut up) = (None, None, None);
+    for component in text.split(", ") {
+        let (speed, direction) = component.split_once(" m/s ").ok_or_else(invalid_velocity)?;
+        let speed = parse_value::<f64>(speed, "speed")?.abs();
+        let (axis, speed) = match direction {
+            "east" => (&mut east, speed),
+            "west" => (&mut east, -speed),
+            "north" => (&mut north, speed),
+            "south" => (&mut north, -speed),
+            "up" => (&mut up, speed),
+            "down" => (&mut up, -speed),
+            _ => return Err(invalid_velocity()),
+        };
+        if axis.replace(speed).is_some() {
+            return Err(invalid_velocity());
+        }
+    }
+    match (east, north, up) {
+        (Some(east), Some(north), Some(up)) => Ok(Some(Velocity { east, north, up })),
+        _ => Err(invalid_velocity()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_kml, write_as_kml, WriteAsKmlOptions};
+
+    const START_TIME: u64 = 1_700_000_000_000;
+
+    fn alarm(record_number: u64) -> Alarm {
+        Alarm {
+            active: [2, 4, 6].contains(&record_number),
+            certainty: 0.25 * (record_number % 4) as f64,
+        }
+    }
+
+    fn identification(record_number: u64) -> Option<String> {
+        [3, 6]
+            .contains(&record_number)
+            .then(|| format!("Drone {}", record_number))
+    }
+
+    fn velocity(record_number: u64) -> Option<Velocity> {
+        (record_number != 1).then(|| Velocity {
+            east: 1.5 * record_number as f64,
+            north: -2.,
+            up: -0.25,
+        })
+    }
+
+    fn tracking_record(
+        record_number: u64,
+        classification: Classification,
+        location: Location,
+    ) -> TrackingRecord {
+        TrackingRecord {
+            time: START_TIME + record_number * 1500,
+            record_number,
+            classification,
+            alarm: alarm(record_number),
+            location,
+            identification: identification(record_number),
+            velocity: velocity(record_number),
+            cuas_location: None,
+        }
+    }
+
+    fn position3d(offset: f64) -> Location {
+        Location::Position3d(Position3d {
+            lat: 51.4507167 + offset,
+            lon: 4.3341194 - offset,
+            height: 120.5 + offset,
+        })
+    }
+
+    fn position2d(offset: f64) -> Location {
+        Location::Position2d(Position2d {
+            lat: 51.4507167 + offset,
+            lon: 4.3341194 - offset,
+        })
+    }
+
+    fn database() -> Database {
+        let detection_record = |record_number: u64, location: Location| DetectionRecord {
+            time: START_TIME + record_number * 1500,
+            record_number,
+            classification: Classification::Unknown,
+            // Inactive alarms with 0% certainty are read as no alarm
+            alarm: Some(Alarm {
+                active: true,
+                certainty: 0.75,
+            }),
+            location: Some(location),
+            identification: identification(record_number),
+            velocity: velocity(record_number),
+            cuas_location: None,
+        };
+
+        Database {
+            detection: vec![Detection {
+                uas_id: Some(3),
+                name: None,
+                records: vec![
+                    detection_record(1, position3d(0.001)),
+                    detection_record(2, position2d(0.002)),
+                ],
+                uav_home_location: None,
+            }],
+            tracks: vec![
+                Track {
+                    uas_id: 7,
+                    name: None,
+                    records: vec![
+                        tracking_record(1, Classification::Uav, position3d(0.)),
+                        tracking_record(2, Classification::Uav, position3d(0.01)),
+                        tracking_record(3, Classification::Gcs, position3d(0.02)),
+                        tracking_record(4, Classification::Uav, position3d(0.03)),
+                    ],
+                    uav_home_location: None,
+                },
+                Track {
+                    uas_id: 8,
+                    name: Some("Intruder".to_owned()),
+                    records: vec![
+                        tracking_record(5, Classification::Other, position2d(0.)),
+                        tracking_record(6, Classification::Other, position2d(0.01)),
+                    ],
+                    uav_home_location: None,
+                },
+                // Its folder follows the one of the previous track in classification order
+                Track {
+                    uas_id: 9,
+                    name: Some("Intruder".to_owned()),
+                    records: vec![tracking_record(7, Classification::Unknown, position3d(0.))],
+                    uav_home_location: None,
+                },
+            ],
+            static_cuas_location: Position3d {
+                lat: 51.45,
+                lon: 4.33,
+                height: 15.,
+            },
+        }
+    }
+
+    fn assert_same_location(location: Location, read: Location) {
+        match (location, read) {
+            (Location::Position3d(pos), Location::Position3d(read)) => assert_eq!(
+                (pos.lat, pos.lon, pos.height),
+                (read.lat, read.lon, read.height)
+            ),
+            (Location::Position2d(pos), Location::Position2d(read)) => {
+                assert_eq!((pos.lat, pos.lon), (read.lat, read.lon))
+            }
+            _ => panic!("location was read as a different kind of location"),
+        }
+    }
+
+    fn velocity_components(velocity: Option<Velocity>) -> Option<(f64, f64, f64)> {
+        velocity.map(|velocity| (velocity.east, velocity.north, velocity.up))
+    }
+
+    #[test]
+    fn round_trip() {
+        let database = database();
+        let mut kml = Vec::new();
+        write_as_kml(
+            database,
+            &mut kml,
+            WriteAsKmlOptions::default().cuas_range(100.),
+        )
+        .unwrap();
+        let read = read_kml(kml.as_slice()).unwrap();
+        let database = self::database();
+
+        assert_eq!(read.detection.len(), database.detection.len());
+        for (detection, read) in database.detection.iter().zip(&read.detection) {
+            assert_eq!(read.uas_id, detection.uas_id);
+            assert_eq!(read.records.len(), detection.records.len());
+            for (record, read) in detection.records.iter().zip(&read.records) {
+                assert_eq!(read.time, record.time);
+                assert_eq!(read.record_number, record.record_number);
+                assert_eq!(read.classification, record.classification);
+                assert_eq!(
+                    read.alarm.map(|alarm| (alarm.active, alarm.certainty)),
+                    record.alarm.map(|alarm| (alarm.active, alarm.certainty))
+                );
+                assert_eq!(read.identification, record.identification);
+                assert_eq!(
+                    velocity_components(read.velocity),
+                    velocity_components(record.velocity)
+                );
+                assert_same_location(record.location.unwrap(), read.location.unwrap());
+            }
+        }
+
+        assert_eq!(read.tracks.len(), database.tracks.len());
+        for (track, read) in database.tracks.iter().zip(&read.tracks) {
+            assert_eq!(read.uas_id, track.uas_id);
+            assert_eq!(read.name, track.name);
+            assert_eq!(read.records.len(), track.records.len());
+            for (record, read) in track.records.iter().zip(&read.records) {
+                assert_eq!(read.time, record.time);
+                assert_eq!(read.record_number, record.record_number);
+                assert_eq!(read.classification, record.classification);
+                assert_eq!(
+                    (read.alarm.active, read.alarm.certainty),
+                    (record.alarm.active, record.alarm.certainty)
+                );
+                assert_eq!(read.identification, record.identification);
+                assert_eq!(
+                    velocity_components(read.velocity),
+                    velocity_components(record.velocity)
+                );
+                assert_same_location(record.location, read.location);
+            }
+        }
+    }
+
+    #[test]
+    fn legacy_velocity() {
+        // Written by versions 0.4.2 and earlier on gx:Tracks
+        let velocity = parse_velocity("-0.25 m/s down, 3 m/s east, -2 m/s south").unwrap();
+        assert_eq!(velocity_components(velocity), Some((3., -2., -0.25)));
+        let velocity = parse_velocity("0 m/s up, -1.5 m/s west, 4 m/s north").unwrap();
+        assert_eq!(velocity_components(velocity), Some((-1.5, 4., 0.)));
+
+        let velocity = parse_velocity("3 m/s east, 2 m/s south, 0.25 m/s down").unwrap();
+        assert_eq!(velocity_components(velocity), Some((3., -2., -0.25)));
+        assert!(parse_velocity("3 m/s east, 2 m/s east, 0 m/s up").is_err());
+        assert!(parse_velocity("3 m/s east, 2 m/s north").is_err());
+    }
+
+    #[test]
+    fn unclassified_track_folders() {
+        let mut database = database();
+        database.tracks[0]
+            .records
+            .retain(|record| record.classification == Classification::Uav);
+        let mut document = build_kml(&database, &WriteAsKmlOptions::default()).unwrap();
+
+        // Versions before 0.4.2 did not split tracks by classification
+        for feature in &mut document.features {
+            let Feature::Folder(folder) = feature else {
+                continue;
+            };
+            if folder.name != "Tracks" {
+                continue;
+            }
+            for feature in &mut folder.features {
+                if let Feature::Folder(track_folder) = feature {
+                    let (base_name, _) = track_folder.name.rsplit_once(" (").unwrap();
+                    track_folder.name = base_name.to_owned();
+                }
+            }
+        }
+
+        let read = database_from_kml(&document).unwrap();
+        assert_eq!(
+            read.tracks
+                .iter()
+                .map(|track| (track.uas_id, track.records.len()))
+                .collect::<Vec<_>>(),
+            [(7, 3), (8, 2), (9, 1)]
+        );
+        assert!(read.tracks[0]
+            .records
+            .iter()
+            .all(|record| record.classification == Classification::Uav));
+        assert!(read.tracks[1]
+            .records
+            .iter()
+            .all(|record| record.classification == Classification::Other));
+    }
+}
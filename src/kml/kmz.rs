@@ -0,0 +1,77 @@
+use std::io::{Seek, Write};
+
+use quick_xml::Writer;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use super::{
+    build_kml,
+    model::Document,
+    serialize::{write_document_end, write_document_start, write_feature},
+    style::BUNDLED_ICONS,
+    WriteAsKmlOptions,
+};
+use crate::{Database, Error};
+
+pub fn write_as_kmz(
+    database: Database,
+    writer: impl Write + Seek,
+    options: WriteAsKmlOptions,
+) -> Result<(), Error> {
+    write_kmz(&build_kml(&database, &options)?, writer)
+}
+
+/// Packages `document` into a KMZ archive, along with copies of the icons it uses so that they can
+/// be shown without an internet connection.
+///
+/// Icon references to any of the icons used by this crate are replaced with references to the
+/// bundled copies. Other icons are left untouched.
+pub fn write_kmz(document: &Document, writer: impl Write + Seek) -> Result<(), Error> {
+    let mut zip = ZipWriter::new(writer);
+    let mut used_icons = Vec::new();
+
+    // Only the styles need to be modified, so avoid copying the features
+    let mut header = Document {
+        name: document.name.clone(),
+        schemas: document.schemas.clone(),
+        styles: document.styles.clone(),
+        features: Vec::new(),
+    };
+    for icon_style in header
+        .styles
+        .iter_mut()
+        .filter_map(|style| style.icon_style.as_mut())
+    {
+        if let Some(icon) = BUNDLED_ICONS
+            .iter()
+            .find(|icon| icon.url == icon_style.href)
+        {
+            icon_style.href = icon.path.to_owned();
+            if !used_icons.contains(&icon.path) {
+                used_icons.push(icon.path);
+            }
+        }
+    }
+
+    // Google Earth uses the first KML file in the archive as the main document
+    zip.start_file("doc.kml", FileOptions::default())?;
+    let mut xml = Writer::new(&mut zip);
+    write_document_start(&mut xml, &header)?;
+    for feature in &document.features {
+        write_feature(&mut xml, feature)?;
+    }
+    write_document_end(&mut xml)?;
+
+    for icon in BUNDLED_ICONS
+        .iter()
+        .filter(|icon| used_icons.contains(&icon.path))
+    {
+        zip.start_file(
+            icon.path,
+            FileOptions::default().compression_method(CompressionMethod::Stored),
+        )?;
+        zip.write_all(icon.data)?;
+    }
+    zip.finish()?;
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use super::{
+    build_kml, header_document,
+    model::{Document, Feature, Folder},
+    write_kml, WriteAsKmlOptions,
+};
+use crate::{Database, Error};
+
+/// Writes several databases into a single KML file. See [`build_many_kml`].
+pub fn write_many_as_kml(
+    sources: Vec<(String, Database)>,
+    writer: impl std::io::Write,
+    options: WriteAsKmlOptions,
+) -> Result<(), Error> {
+    write_kml(&build_many_kml(&sources, &options)?, writer)
+}
+
+/// Builds a single KML [`Document`] representing several databases, given along with the name of
+/// their source. Useful for comparing the recordings of different C-UAS systems on the same time
+/// slider.
+///
+/// Each database is placed inside a top-level folder named after its source, with its own CUAS
+/// placemark at its `static_cuas_location`. The styles of each source are copied with their ids
+/// prefixed by `source<N>_` (Starting at 1), so that they can be edited separately.
+///
+/// `options.static_cuas_location`, if given, overrides the location of every source.
+pub fn build_many_kml(
+    sources: &[(String, Database)],
+    options: &WriteAsKmlOptions,
+) -> Result<Document, Error> {
+    let mut document = Document {
+        styles: Vec::new(),
+        ..header_document(options)
+    };
+
+    for (idx, (name, database)) in sources.iter().enumerate() {
+        let prefix = format!("source{}_", idx + 1);
+        let source = build_kml(database, options)?;
+        document
+            .styles
+            .extend(source.styles.into_iter().map(|mut style| {
+                style.id.insert_str(0, &prefix);
+                style
+            }));
+        let mut features = source.features;
+        for feature in &mut features {
+            prefix_style_urls(feature, &prefix);
+        }
+        document.features.push(Feature::Folder(Folder {
+            name: name.clone(),
+            description: None,
+            features,
+        }));
+    }
+
+    Ok(document)
+}
+
+fn prefix_style_urls(feature: &mut Feature, prefix: &str) {
+    match feature {
+        Feature::Folder(folder) => folder
+            .features
+            .iter_mut()
+            .for_each(|feature| prefix_style_urls(feature, prefix)),
+        Feature::Placemark(placemark) => {
+            if let Some(style_url) = &mut placemark.style_url {
+                style_url.insert_str(0, prefix);
+            }
+        }
+    }
+}
@@ -1,29 +1,53 @@
-use crate::Database;
-use quick_xml::Writer;
+//! An in-memory representation of the KML documents generated by this crate.
+//!
+//! [`build_kml`] converts a [`Database`] into a [`Document`] which can be freely modified before
+//! being serialized with [`write_kml`]. [`parse_kml`] does the opposite, and [`database_from_kml`]
+//! recovers the [`Database`] a document was built from.
+
+use crate::{record::check_cuas_location, Database, Error};
+use courageous_format::Position3d;
 
 use self::{
-    cuas::write_cuas_origin, detection::write_detection_set, ext_data::write_schema,
-    style::write_style, tracking::write_track_set,
+    cuas::cuas_origin_placemark, detection::detection_set_folder, ext_data::schema, style::styles,
+    tracking::track_set_folder,
 };
 
 mod cuas;
 mod detection;
 mod ext_data;
-mod geometry;
+pub(crate) mod geometry;
+mod import;
+mod kmz;
+mod merge;
+mod model;
+mod parse;
+pub(crate) mod serialize;
+mod skipped;
+mod stream;
 mod style;
 mod tracking;
 mod uav_home_location;
 
-const KML_DOCUMENT_ATTRIBUTES: [(&str, &str); 2] = [
-    ("xmlns", "http://www.opengis.net/kml/2.2"),
-    ("xmlns:gx", "http://www.google.com/kml/ext/2.2"),
-];
+pub use import::{database_from_kml, read_kml};
+pub use kmz::{write_as_kmz, write_kmz};
+pub use merge::{build_many_kml, write_many_as_kml};
+pub use model::*;
+pub use parse::parse_kml;
+pub use serialize::write_kml;
+pub use skipped::skipped_records_folder;
+pub use stream::{stream_as_kml, stream_as_kml_reopening};
 
 #[derive(Clone, Default)]
 #[non_exhaustive]
 pub struct WriteAsKmlOptions {
     pub disable_track_icons: bool,
     pub cuas_range: f64,
+    /// Draws `Bearing` and `BearingElevation` locations of detection records as points at
+    /// `cuas_range` from their CUAS location, rather than as rays. Meant for sensors that only
+    /// measure angles, whose detections would otherwise hide each other behind a bundle of rays.
+    pub angle_points: bool,
+    /// Overrides the `static_cuas_location` member of the database if given.
+    pub static_cuas_location: Option<Position3d>,
 }
 
 impl WriteAsKmlOptions {
@@ -35,42 +59,57 @@ impl WriteAsKmlOptions {
         self.cuas_range = val;
         self
     }
+    pub fn angle_points(mut self, val: bool) -> WriteAsKmlOptions {
+        self.angle_points = val;
+        self
+    }
+    pub fn static_cuas_location(mut self, val: Option<Position3d>) -> WriteAsKmlOptions {
+        self.static_cuas_location = val;
+        self
+    }
 }
 
 pub fn write_as_kml(
     database: Database,
     writer: impl std::io::Write,
     options: WriteAsKmlOptions,
-) -> anyhow::Result<()> {
-    let mut xml = Writer::new(writer);
-    let cuas_range = options.cuas_range;
-    xml.write_bom()?;
-    xml.create_element("kml")
-        .with_attributes(KML_DOCUMENT_ATTRIBUTES)
-        .write_inner_content(|x| {
-            x.create_element("Document").write_inner_content(|x| {
-                write_schema(x)?;
-                write_style(x, &options)?;
-
-                write_detection_set(
-                    x,
-                    &database.detection,
-                    database.static_cuas_location.clone(),
-                    cuas_range,
-                )?;
-                write_track_set(
-                    x,
-                    &database.tracks,
-                    database.static_cuas_location.clone(),
-                    cuas_range,
-                )?;
-                write_cuas_origin(x, database.static_cuas_location.clone())?;
+) -> Result<(), Error> {
+    write_kml(&build_kml(&database, &options)?, writer)
+}
 
-                Ok(())
-            })?;
+/// Builds the KML [`Document`] representing `database` without serializing it.
+pub fn build_kml(database: &Database, options: &WriteAsKmlOptions) -> Result<Document, Error> {
+    let cuas_range = options.cuas_range;
+    let static_cuas_location = options
+        .static_cuas_location
+        .unwrap_or(database.static_cuas_location);
+    check_cuas_location(static_cuas_location)?;
 
-            Ok(())
-        })?;
+    Ok(Document {
+        features: vec![
+            Feature::Folder(detection_set_folder(
+                &database.detection,
+                static_cuas_location,
+                cuas_range,
+                options.angle_points,
+            )?),
+            Feature::Folder(track_set_folder(
+                &database.tracks,
+                static_cuas_location,
+                cuas_range,
+            )?),
+            Feature::Placemark(cuas_origin_placemark(static_cuas_location)),
+        ],
+        ..header_document(options)
+    })
+}
 
-    Ok(())
+/// Creates a document with the schemas and styles used by the generated KML, but no features.
+fn header_document(options: &WriteAsKmlOptions) -> Document {
+    Document {
+        name: None,
+        schemas: vec![schema()],
+        styles: styles(options),
+        features: Vec::new(),
+    }
 }
@@ -0,0 +1,191 @@
+use courageous_format::Position3d;
+use time::OffsetDateTime;
+
+/// Root of a KML file: A `<Document>` inside a `<kml>` element.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    pub name: Option<String>,
+    pub schemas: Vec<Schema>,
+    pub styles: Vec<Style>,
+    pub features: Vec<Feature>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Feature {
+    Folder(Folder),
+    Placemark(Placemark),
+}
+
+impl Feature {
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Feature::Folder(folder) => Some(&folder.name),
+            Feature::Placemark(placemark) => placemark.name.as_deref(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Folder {
+    pub name: String,
+    pub description: Option<String>,
+    pub features: Vec<Feature>,
+}
+
+impl Folder {
+    /// Returns the first direct child folder with the given name.
+    pub fn folder_mut(&mut self, name: &str) -> Option<&mut Folder> {
+        self.features.iter_mut().find_map(|feature| match feature {
+            Feature::Folder(folder) if folder.name == name => Some(folder),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Placemark {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub style_url: Option<String>,
+    pub time: Option<TimePrimitive>,
+    pub extended_data: Option<ExtendedData>,
+    pub geometry: Option<Geometry>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimePrimitive {
+    TimeStamp(OffsetDateTime),
+    TimeSpan {
+        begin: Option<OffsetDateTime>,
+        end: Option<OffsetDateTime>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AltitudeMode {
+    #[default]
+    ClampToGround,
+    RelativeToGround,
+    Absolute,
+}
+
+impl AltitudeMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AltitudeMode::ClampToGround => "clampToGround",
+            AltitudeMode::RelativeToGround => "relativeToGround",
+            AltitudeMode::Absolute => "absolute",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Geometry {
+    Point(Point),
+    LineString(LineString),
+    Polygon(Polygon),
+    /// A `gx:MultiTrack`: A set of time-tagged paths.
+    MultiTrack(MultiTrack),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    pub extrude: bool,
+    pub altitude_mode: AltitudeMode,
+    pub coordinates: Position3d,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineString {
+    pub extrude: bool,
+    pub tessellate: bool,
+    pub altitude_mode: AltitudeMode,
+    pub coordinates: Vec<Position3d>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polygon {
+    pub extrude: bool,
+    pub tessellate: bool,
+    pub altitude_mode: AltitudeMode,
+    pub outer_boundary: Vec<Position3d>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MultiTrack {
+    pub altitude_mode: AltitudeMode,
+    pub tracks: Vec<GxTrack>,
+}
+
+/// A `gx:Track`. `when` and `coords` must have the same length.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GxTrack {
+    pub altitude_mode: AltitudeMode,
+    pub when: Vec<OffsetDateTime>,
+    pub coords: Vec<Position3d>,
+    pub extended_data: Option<ExtendedData>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExtendedData {
+    /// URL of the [`Schema`] the data follows, e.g. `#schema`.
+    pub schema_url: String,
+    pub simple_data: Vec<SimpleData>,
+    /// `gx:SimpleArrayData` elements, used for per-point data of a [`GxTrack`].
+    pub simple_array_data: Vec<SimpleArrayData>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimpleData {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimpleArrayData {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schema {
+    pub id: String,
+    pub name: String,
+    pub fields: Vec<SimpleField>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimpleField {
+    pub name: String,
+    pub field_type: String,
+    /// HTML shown next to the value of the field. Written as CDATA.
+    pub display_name: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Style {
+    pub id: String,
+    pub icon_style: Option<IconStyle>,
+    pub line_style: Option<LineStyle>,
+    pub balloon_style: Option<BalloonStyle>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IconStyle {
+    /// Color in `aabbggrr` format.
+    pub color: Option<String>,
+    pub scale: f64,
+    pub href: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineStyle {
+    pub random_color: bool,
+    pub width: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BalloonStyle {
+    /// HTML shown in the balloon. Written as CDATA.
+    pub text: String,
+}
@@ -0,0 +1,309 @@
+use std::{io::BufRead, str::FromStr};
+
+use courageous_format::Position3d;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use super::model::{
+    AltitudeMode, BalloonStyle, Document, ExtendedData, Feature, Folder, Geometry, GxTrack,
+    IconStyle, LineString, LineStyle, MultiTrack, Placemark, Point, Polygon, Schema,
+    SimpleArrayData, SimpleData, SimpleField, Style, TimePrimitive,
+};
+use crate::{
+    xml::{read_element_tree, Element},
+    Error,
+};
+
+/// Parses a KML document, as written by [`write_kml`](super::write_kml), from `reader`.
+///
+/// Only the elements and attributes that can be represented by [`Document`] are read; anything else
+/// is ignored.
+pub fn parse_kml(reader: impl BufRead) -> Result<Document, Error> {
+    let root = read_element_tree(reader)?;
+    if root.name != "kml" {
+        return Err(invalid(format!(
+            "expected a <kml> root element, found <{}>",
+            root.name
+        )));
+    }
+    let document = required_child(&root, "Document")?;
+
+    Ok(Document {
+        name: document.child_text("name").map(str::to_owned),
+        schemas: document
+            .children("Schema")
+            .map(parse_schema)
+            .collect::<Result<_, _>>()?,
+        styles: document
+            .children("Style")
+            .map(parse_style)
+            .collect::<Result<_, _>>()?,
+        features: parse_features(document)?,
+    })
+}
+
+fn parse_schema(element: &Element) -> Result<Schema, Error> {
+    Ok(Schema {
+        id: required_attribute(element, "id")?.to_owned(),
+        name: required_attribute(element, "name")?.to_owned(),
+        fields: element
+            .children("SimpleField")
+            .map(|field| {
+                Ok(SimpleField {
+                    name: required_attribute(field, "name")?.to_owned(),
+                    field_type: required_attribute(field, "type")?.to_owned(),
+                    display_name: field.child_text("displayName").unwrap_or("").to_owned(),
+                })
+            })
+            .collect::<Result<_, Error>>()?,
+    })
+}
+
+fn parse_style(element: &Element) -> Result<Style, Error> {
+    Ok(Style {
+        id: required_attribute(element, "id")?.to_owned(),
+        icon_style: element
+            .child("IconStyle")
+            .map(|icon_style| {
+                Ok::<_, Error>(IconStyle {
+                    color: icon_style.child_text("color").map(str::to_owned),
+                    scale: icon_style
+                        .child_text("scale")
+                        .map_or(Ok(1.), |scale| parse_value(scale, "scale"))?,
+                    href: required_child(icon_style, "Icon")?
+                        .child_text("href")
+                        .unwrap_or("")
+                        .to_owned(),
+                })
+            })
+            .transpose()?,
+        line_style: element
+            .child("LineStyle")
+            .map(|line_style| {
+                Ok::<_, Error>(LineStyle {
+                    random_color: line_style.child_text("colorMode") == Some("random"),
+                    width: line_style
+                        .child_text("width")
+                        .map_or(Ok(1.), |width| parse_value(width, "width"))?,
+                })
+            })
+            .transpose()?,
+        balloon_style: element
+            .child("BalloonStyle")
+            .map(|balloon_style| BalloonStyle {
+                text: balloon_style.child_text("text").unwrap_or("").to_owned(),
+            }),
+    })
+}
+
+fn parse_features(element: &Element) -> Result<Vec<Feature>, Error> {
+    element
+        .children
+        .iter()
+        .filter_map(|child| match child.name.as_str() {
+            "Folder" => Some(parse_folder(child).map(Feature::Folder)),
+            "Placemark" => Some(parse_placemark(child).map(Feature::Placemark)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn parse_folder(element: &Element) -> Result<Folder, Error> {
+    Ok(Folder {
+        name: element.child_text("name").unwrap_or("").to_owned(),
+        description: element.child_text("description").map(str::to_owned),
+        features: parse_features(element)?,
+    })
+}
+
+fn parse_placemark(element: &Element) -> Result<Placemark, Error> {
+    let time = if let Some(time_stamp) = element.child("TimeStamp") {
+        Some(TimePrimitive::TimeStamp(parse_time(
+            time_stamp
+                .child_text("when")
+                .ok_or_else(|| invalid("<TimeStamp> has no <when> element"))?,
+        )?))
+    } else if let Some(time_span) = element.child("TimeSpan") {
+        Some(TimePrimitive::TimeSpan {
+            begin: time_span.child_text("begin").map(parse_time).transpose()?,
+            end: time_span.child_text("end").map(parse_time).transpose()?,
+        })
+    } else {
+        None
+    };
+
+    Ok(Placemark {
+        name: element.child_text("name").map(str::to_owned),
+        description: element.child_text("description").map(str::to_owned),
+        style_url: element.child_text("styleUrl").map(str::to_owned),
+        time,
+        extended_data: element
+            .child("ExtendedData")
+            .map(parse_extended_data)
+            .transpose()?,
+        geometry: element
+            .children
+            .iter()
+            .find_map(|child| parse_geometry(child).transpose())
+            .transpose()?,
+    })
+}
+
+/// Returns `None` if `element` is not a geometry.
+fn parse_geometry(element: &Element) -> Result<Option<Geometry>, Error> {
+    Ok(Some(match element.name.as_str() {
+        "Point" => Geometry::Point(Point {
+            extrude: parse_bool(element.child_text("extrude"))?,
+            altitude_mode: parse_altitude_mode(element.child_text("altitudeMode"))?,
+            coordinates: parse_coordinate(
+                element
+                    .child_text("coordinates")
+                    .ok_or_else(|| invalid("<Point> has no <coordinates> element"))?,
+                ',',
+            )?,
+        }),
+        "LineString" => Geometry::LineString(LineString {
+            extrude: parse_bool(element.child_text("extrude"))?,
+            tessellate: parse_bool(element.child_text("tessellate"))?,
+            altitude_mode: parse_altitude_mode(element.child_text("altitudeMode"))?,
+            coordinates: parse_coordinates(element.child_text("coordinates").unwrap_or(""))?,
+        }),
+        "Polygon" => Geometry::Polygon(Polygon {
+            extrude: parse_bool(element.child_text("extrude"))?,
+            tessellate: parse_bool(element.child_text("tessellate"))?,
+            altitude_mode: parse_altitude_mode(element.child_text("altitudeMode"))?,
+            outer_boundary: parse_coordinates(
+                required_child(required_child(element, "outerBoundaryIs")?, "LinearRing")?
+                    .child_text("coordinates")
+                    .unwrap_or(""),
+            )?,
+        }),
+        "gx:MultiTrack" => Geometry::MultiTrack(MultiTrack {
+            altitude_mode: parse_altitude_mode(element.child_text("altitudeMode"))?,
+            tracks: element
+                .children("gx:Track")
+                .map(parse_gx_track)
+                .collect::<Result<_, _>>()?,
+        }),
+        _ => return Ok(None),
+    }))
+}
+
+fn parse_gx_track(element: &Element) -> Result<GxTrack, Error> {
+    let track = GxTrack {
+        altitude_mode: parse_altitude_mode(element.child_text("altitudeMode"))?,
+        when: element
+            .children("when")
+            .map(|when| parse_time(when.text.trim()))
+            .collect::<Result<_, _>>()?,
+        coords: element
+            .children("gx:coord")
+            .map(|coord| parse_coordinate(coord.text.trim(), ' '))
+            .collect::<Result<_, _>>()?,
+        extended_data: element
+            .child("ExtendedData")
+            .map(parse_extended_data)
+            .transpose()?,
+    };
+    if track.when.len() != track.coords.len() {
+        return Err(invalid(format!(
+            "<gx:Track> has {} <when> elements but {} <gx:coord> elements",
+            track.when.len(),
+            track.coords.len()
+        )));
+    }
+    Ok(track)
+}
+
+fn parse_extended_data(element: &Element) -> Result<ExtendedData, Error> {
+    let schema_data = required_child(element, "SchemaData")?;
+    Ok(ExtendedData {
+        schema_url: schema_data.attribute("schemaUrl").unwrap_or("").to_owned(),
+        simple_data: schema_data
+            .children("SimpleData")
+            .map(|data| {
+                Ok(SimpleData {
+                    name: required_attribute(data, "name")?.to_owned(),
+                    value: data.text.trim().to_owned(),
+                })
+            })
+            .collect::<Result<_, Error>>()?,
+        simple_array_data: schema_data
+            .children("gx:SimpleArrayData")
+            .map(|data| {
+                Ok(SimpleArrayData {
+                    name: required_attribute(data, "name")?.to_owned(),
+                    values: data
+                        .children("gx:value")
+                        .map(|value| value.text.trim().to_owned())
+                        .collect(),
+                })
+            })
+            .collect::<Result<_, Error>>()?,
+    })
+}
+
+fn required_child<'a>(element: &'a Element, name: &str) -> Result<&'a Element, Error> {
+    element
+        .child(name)
+        .ok_or_else(|| invalid(format!("<{}> has no <{}> element", element.name, name)))
+}
+
+fn required_attribute<'a>(element: &'a Element, name: &str) -> Result<&'a str, Error> {
+    element
+        .attribute(name)
+        .ok_or_else(|| invalid(format!("<{}> has no {} attribute", element.name, name)))
+}
+
+fn parse_bool(text: Option<&str>) -> Result<bool, Error> {
+    match text {
+        None | Some("0") | Some("false") => Ok(false),
+        Some("1") | Some("true") => Ok(true),
+        Some(text) => Err(invalid(format!("invalid boolean {:?}", text))),
+    }
+}
+
+fn parse_altitude_mode(text: Option<&str>) -> Result<AltitudeMode, Error> {
+    match text {
+        None | Some("clampToGround") => Ok(AltitudeMode::ClampToGround),
+        Some("relativeToGround") => Ok(AltitudeMode::RelativeToGround),
+        Some("absolute") => Ok(AltitudeMode::Absolute),
+        Some(text) => Err(invalid(format!("invalid altitude mode {:?}", text))),
+    }
+}
+
+/// Parses a `lon<separator>lat[<separator>height]` tuple.
+fn parse_coordinate(text: &str, separator: char) -> Result<Position3d, Error> {
+    let mut values = text.split(separator).map(str::trim);
+    let mut next = |what| {
+        values
+            .next()
+            .ok_or_else(|| invalid(format!("missing {} in coordinate {:?}", what, text)))
+    };
+    let lon = parse_value(next("longitude")?, "longitude")?;
+    let lat = parse_value(next("latitude")?, "latitude")?;
+    let height = match values.next() {
+        Some(height) => parse_value(height, "height")?,
+        None => 0.,
+    };
+    Ok(Position3d { lat, lon, height })
+}
+
+fn parse_coordinates(text: &str) -> Result<Vec<Position3d>, Error> {
+    text.split_whitespace()
+        .map(|coordinate| parse_coordinate(coordinate, ','))
+        .collect()
+}
+
+fn parse_time(text: &str) -> Result<OffsetDateTime, Error> {
+    OffsetDateTime::parse(text, &Rfc3339)
+        .map_err(|err| invalid(format!("invalid time {:?}: {}", text, err)))
+}
+
+pub(super) fn parse_value<T: FromStr>(text: &str, what: &str) -> Result<T, Error> {
+    text.parse()
+        .map_err(|_| invalid(format!("invalid {} {:?}", what, text)))
+}
+
+pub(super) fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidKml(message.into())
+}
@@ -0,0 +1,351 @@
+use std::io::Write;
+
+use courageous_format::Position3d;
+use quick_xml::{
+    events::{BytesCData, BytesEnd, BytesStart, BytesText, Event},
+    Writer,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use super::model::{
+    Document, ExtendedData, Feature, Folder, Geometry, Placemark, Schema, Style, TimePrimitive,
+};
+use crate::Error;
+
+const KML_DOCUMENT_ATTRIBUTES: [(&str, &str); 2] = [
+    ("xmlns", "http://www.opengis.net/kml/2.2"),
+    ("xmlns:gx", "http://www.google.com/kml/ext/2.2"),
+];
+
+/// Serializes a KML [`Document`] into `writer`.
+pub fn write_kml(document: &Document, writer: impl Write) -> Result<(), Error> {
+    let mut xml = Writer::new(writer);
+    write_document_start(&mut xml, document)?;
+    for feature in &document.features {
+        write_feature(&mut xml, feature)?;
+    }
+    write_document_end(&mut xml)?;
+
+    Ok(())
+}
+
+/// Writes everything in `document` up to its features, leaving the `<Document>` element open so
+/// that features can be written one by one.
+pub(crate) fn write_document_start(
+    x: &mut Writer<impl Write>,
+    document: &Document,
+) -> Result<(), Error> {
+    x.write_bom()?;
+    x.write_event(Event::Start(
+        BytesStart::new("kml").with_attributes(KML_DOCUMENT_ATTRIBUTES),
+    ))?;
+    x.write_event(Event::Start(BytesStart::new("Document")))?;
+    if let Some(name) = &document.name {
+        x.create_element("name")
+            .write_text_content(BytesText::new(name))?;
+    }
+    for schema in &document.schemas {
+        write_schema(x, schema)?;
+    }
+    for style in &document.styles {
+        write_style(x, style)?;
+    }
+    Ok(())
+}
+
+/// Closes the elements opened by [`write_document_start`].
+pub(crate) fn write_document_end(x: &mut Writer<impl Write>) -> Result<(), Error> {
+    x.write_event(Event::End(BytesEnd::new("Document")))?;
+    x.write_event(Event::End(BytesEnd::new("kml")))?;
+    Ok(())
+}
+
+/// Opens a `<Folder>` element named `name`, leaving it open so that features can be written into
+/// it one by one. Must be closed with [`write_folder_end`].
+pub(crate) fn write_folder_start(x: &mut Writer<impl Write>, name: &str) -> Result<(), Error> {
+    x.write_event(Event::Start(BytesStart::new("Folder")))?;
+    x.create_element("name")
+        .write_text_content(BytesText::new(name))?;
+    Ok(())
+}
+
+pub(crate) fn write_folder_end(x: &mut Writer<impl Write>) -> Result<(), Error> {
+    x.write_event(Event::End(BytesEnd::new("Folder")))?;
+    Ok(())
+}
+
+/// Writes an element named `name` whose content is generated by `inner`.
+///
+/// Unlike [`quick_xml::writer::ElementWriter::write_inner_content`], `inner` may fail with any
+/// [`Error`], not only XML ones.
+pub(crate) fn write_element<W: Write>(
+    x: &mut Writer<W>,
+    name: &str,
+    inner: impl FnOnce(&mut Writer<W>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    x.write_event(Event::Start(BytesStart::new(name)))?;
+    inner(x)?;
+    x.write_event(Event::End(BytesEnd::new(name)))?;
+    Ok(())
+}
+
+fn write_schema(x: &mut Writer<impl Write>, schema: &Schema) -> Result<(), Error> {
+    x.create_element("Schema")
+        .with_attribute(("name", schema.name.as_str()))
+        .with_attribute(("id", schema.id.as_str()))
+        .write_inner_content(|x| {
+            for field in &schema.fields {
+                x.create_element("SimpleField")
+                    .with_attribute(("name", field.name.as_str()))
+                    .with_attribute(("type", field.field_type.as_str()))
+                    .write_inner_content(|x| {
+                        x.create_element("displayName").write_inner_content(|x| {
+                            x.write_event(Event::CData(BytesCData::new(&field.display_name)))
+                        })?;
+                        Ok(())
+                    })?;
+            }
+            Ok(())
+        })?;
+    Ok(())
+}
+
+fn write_style(x: &mut Writer<impl Write>, style: &Style) -> Result<(), Error> {
+    x.create_element("Style")
+        .with_attribute(("id", style.id.as_str()))
+        .write_inner_content(|x| {
+            if let Some(icon_style) = &style.icon_style {
+                x.create_element("IconStyle").write_inner_content(|x| {
+                    if let Some(color) = &icon_style.color {
+                        x.create_element("color")
+                            .write_text_content(BytesText::new(color))?;
+                    }
+                    x.create_element("scale")
+                        .write_text_content(BytesText::new(&icon_style.scale.to_string()))?;
+                    x.create_element("Icon").write_inner_content(|x| {
+                        x.create_element("href")
+                            .write_text_content(BytesText::new(&icon_style.href))?;
+                        Ok(())
+                    })?;
+                    Ok(())
+                })?;
+            }
+            if let Some(line_style) = &style.line_style {
+                x.create_element("LineStyle").write_inner_content(|x| {
+                    if line_style.random_color {
+                        x.create_element("colorMode")
+                            .write_text_content(BytesText::new("random"))?;
+                    }
+                    x.create_element("width")
+                        .write_text_content(BytesText::new(&line_style.width.to_string()))?;
+                    Ok(())
+                })?;
+            }
+            if let Some(balloon_style) = &style.balloon_style {
+                x.create_element("BalloonStyle").write_inner_content(|x| {
+                    x.create_element("text").write_inner_content(|x| {
+                        x.write_event(Event::CData(BytesCData::new(&balloon_style.text)))?;
+                        Ok(())
+                    })?;
+                    Ok(())
+                })?;
+            }
+            Ok(())
+        })?;
+    Ok(())
+}
+
+pub(crate) fn write_feature(x: &mut Writer<impl Write>, feature: &Feature) -> Result<(), Error> {
+    match feature {
+        Feature::Folder(folder) => write_folder(x, folder),
+        Feature::Placemark(placemark) => write_placemark(x, placemark),
+    }
+}
+
+fn write_folder(x: &mut Writer<impl Write>, folder: &Folder) -> Result<(), Error> {
+    write_element(x, "Folder", |x| {
+        x.create_element("name")
+            .write_text_content(BytesText::new(&folder.name))?;
+        if let Some(description) = &folder.description {
+            x.create_element("description")
+                .write_text_content(BytesText::new(description))?;
+        }
+        for feature in &folder.features {
+            write_feature(x, feature)?;
+        }
+        Ok(())
+    })
+}
+
+fn write_placemark(x: &mut Writer<impl Write>, placemark: &Placemark) -> Result<(), Error> {
+    write_element(x, "Placemark", |x| {
+        if let Some(name) = &placemark.name {
+            x.create_element("name")
+                .write_text_content(BytesText::new(name))?;
+        }
+        if let Some(description) = &placemark.description {
+            x.create_element("description")
+                .write_text_content(BytesText::new(description))?;
+        }
+        match placemark.time {
+            Some(TimePrimitive::TimeStamp(when)) => write_element(x, "TimeStamp", |x| {
+                x.create_element("when")
+                    .write_text_content(BytesText::new(&format_time(when)?))?;
+                Ok(())
+            })?,
+            Some(TimePrimitive::TimeSpan { begin, end }) => write_element(x, "TimeSpan", |x| {
+                if let Some(begin) = begin {
+                    x.create_element("begin")
+                        .write_text_content(BytesText::new(&format_time(begin)?))?;
+                }
+                if let Some(end) = end {
+                    x.create_element("end")
+                        .write_text_content(BytesText::new(&format_time(end)?))?;
+                }
+                Ok(())
+            })?,
+            None => (),
+        }
+        if let Some(style_url) = &placemark.style_url {
+            x.create_element("styleUrl")
+                .write_text_content(BytesText::new(style_url))?;
+        }
+        if let Some(extended_data) = &placemark.extended_data {
+            write_extended_data(x, extended_data)?;
+        }
+        if let Some(geometry) = &placemark.geometry {
+            write_geometry(x, geometry)?;
+        }
+        Ok(())
+    })
+}
+
+fn write_geometry(x: &mut Writer<impl Write>, geometry: &Geometry) -> Result<(), Error> {
+    match geometry {
+        Geometry::Point(point) => {
+            x.create_element("Point").write_inner_content(|x| {
+                x.create_element("extrude")
+                    .write_text_content(BytesText::new(bool_str(point.extrude)))?;
+                x.create_element("altitudeMode")
+                    .write_text_content(BytesText::new(point.altitude_mode.as_str()))?;
+                x.create_element("coordinates")
+                    .write_text_content(BytesText::new(&coordinate_str(point.coordinates)))?;
+                Ok(())
+            })?;
+        }
+        Geometry::LineString(line) => {
+            x.create_element("LineString").write_inner_content(|x| {
+                x.create_element("extrude")
+                    .write_text_content(BytesText::new(bool_str(line.extrude)))?;
+                x.create_element("tessellate")
+                    .write_text_content(BytesText::new(bool_str(line.tessellate)))?;
+                x.create_element("altitudeMode")
+                    .write_text_content(BytesText::new(line.altitude_mode.as_str()))?;
+                x.create_element("coordinates")
+                    .write_text_content(BytesText::new(&coordinates_str(&line.coordinates)))?;
+                Ok(())
+            })?;
+        }
+        Geometry::Polygon(polygon) => {
+            x.create_element("Polygon").write_inner_content(|x| {
+                x.create_element("extrude")
+                    .write_text_content(BytesText::new(bool_str(polygon.extrude)))?;
+                x.create_element("tessellate")
+                    .write_text_content(BytesText::new(bool_str(polygon.tessellate)))?;
+                x.create_element("altitudeMode")
+                    .write_text_content(BytesText::new(polygon.altitude_mode.as_str()))?;
+                x.create_element("outerBoundaryIs")
+                    .write_inner_content(|x| {
+                        x.create_element("LinearRing").write_inner_content(|x| {
+                            x.create_element("coordinates")
+                                .write_text_content(BytesText::new(&coordinates_str(
+                                    &polygon.outer_boundary,
+                                )))?;
+                            Ok(())
+                        })?;
+                        Ok(())
+                    })?;
+                Ok(())
+            })?;
+        }
+        Geometry::MultiTrack(multi_track) => {
+            write_element(x, "gx:MultiTrack", |x| {
+                x.create_element("altitudeMode")
+                    .write_text_content(BytesText::new(multi_track.altitude_mode.as_str()))?;
+                for track in &multi_track.tracks {
+                    write_element(x, "gx:Track", |x| {
+                        x.create_element("altitudeMode")
+                            .write_text_content(BytesText::new(track.altitude_mode.as_str()))?;
+                        for (when, pos) in track.when.iter().zip(&track.coords) {
+                            x.create_element("when")
+                                .write_text_content(BytesText::new(&format_time(*when)?))?;
+                            x.create_element("gx:coord")
+                                .write_text_content(BytesText::new(&format!(
+                                    "{} {} {}",
+                                    pos.lon, pos.lat, pos.height
+                                )))?;
+                        }
+                        if let Some(extended_data) = &track.extended_data {
+                            write_extended_data(x, extended_data)?;
+                        }
+                        Ok(())
+                    })?;
+                }
+                Ok(())
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn write_extended_data(x: &mut Writer<impl Write>, data: &ExtendedData) -> Result<(), Error> {
+    x.create_element("ExtendedData").write_inner_content(|x| {
+        x.create_element("SchemaData")
+            .with_attribute(("schemaUrl", data.schema_url.as_str()))
+            .write_inner_content(|x| {
+                for simple_data in &data.simple_data {
+                    x.create_element("SimpleData")
+                        .with_attribute(("name", simple_data.name.as_str()))
+                        .write_text_content(BytesText::new(&simple_data.value))?;
+                }
+                for array_data in &data.simple_array_data {
+                    x.create_element("gx:SimpleArrayData")
+                        .with_attribute(("name", array_data.name.as_str()))
+                        .write_inner_content(|x| {
+                            for value in &array_data.values {
+                                x.create_element("gx:value")
+                                    .write_text_content(BytesText::new(value))?;
+                            }
+                            Ok(())
+                        })?;
+                }
+                Ok(())
+            })?;
+        Ok(())
+    })?;
+    Ok(())
+}
+
+fn format_time(time: OffsetDateTime) -> Result<String, Error> {
+    Ok(time.format(&Rfc3339)?)
+}
+
+fn bool_str(val: bool) -> &'static str {
+    if val {
+        "1"
+    } else {
+        "0"
+    }
+}
+
+fn coordinate_str(pos: Position3d) -> String {
+    format!("{},{},{}", pos.lon, pos.lat, pos.height)
+}
+
+fn coordinates_str(positions: &[Position3d]) -> String {
+    positions
+        .iter()
+        .map(|&pos| coordinate_str(pos))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
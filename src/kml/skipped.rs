@@ -0,0 +1,45 @@
+use courageous_format::Position3d;
+
+use super::model::{AltitudeMode, Feature, Folder, Geometry, Placemark, Point, TimePrimitive};
+use crate::{record::record_time, SkippedRecord};
+
+/// Creates a folder marking the records skipped by
+/// [`read_document_lenient`](crate::read_document_lenient), to be added to a document built by
+/// [`build_kml`](super::build_kml).
+///
+/// Each record is placed at the last absolute position of its set before it, or at
+/// `static_cuas_location` if there is none, and described by the reason it was skipped.
+pub fn skipped_records_folder(
+    skipped: &[SkippedRecord],
+    static_cuas_location: Position3d,
+) -> Folder {
+    Folder {
+        name: "Skipped Records".to_owned(),
+        description: Some(format!("{} records could not be read", skipped.len())),
+        features: skipped
+            .iter()
+            .map(|record| {
+                Feature::Placemark(Placemark {
+                    name: Some(match record.record_number {
+                        Some(record_number) => format!("Skipped record {}", record_number),
+                        None => "Skipped record".to_owned(),
+                    }),
+                    description: Some(record.to_string()),
+                    // Invalid times are shown as having no time instead
+                    time: record
+                        .time
+                        .and_then(|time| {
+                            record_time(time, record.set, record.record_number.unwrap_or(0)).ok()
+                        })
+                        .map(TimePrimitive::TimeStamp),
+                    geometry: Some(Geometry::Point(Point {
+                        extrude: false,
+                        altitude_mode: AltitudeMode::Absolute,
+                        coordinates: record.last_position.unwrap_or(static_cuas_location),
+                    })),
+                    ..Default::default()
+                })
+            })
+            .collect(),
+    }
+}
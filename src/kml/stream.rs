@@ -0,0 +1,254 @@
+use std::{
+    fmt,
+    io::{Read, Seek, Write},
+};
+
+use courageous_format::{Detection, Position3d, Track};
+use quick_xml::Writer;
+use serde::{
+    de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+
+use super::{
+    cuas::cuas_origin_placemark,
+    detection::detection_folder,
+    header_document,
+    model::Feature,
+    serialize::{
+        write_document_end, write_document_start, write_feature, write_folder_end,
+        write_folder_start,
+    },
+    tracking::classified_track_folders,
+    WriteAsKmlOptions,
+};
+use crate::{record::check_cuas_location, Error, RecordSet};
+
+/// Converts a COURAGEOUS document read from `reader` into KML, without loading the whole document
+/// into memory.
+///
+/// Detection sets and tracks are deserialized and written one at a time, so memory usage is bounded
+/// by the largest one of them. Unless [`WriteAsKmlOptions::static_cuas_location`] is set, the input
+/// is read twice: once to find the static CUAS location, and once more to convert it.
+///
+/// `reader` is not buffered internally, so wrapping it in a [`std::io::BufReader`] is recommended.
+/// See [`stream_as_kml_reopening`] for inputs that cannot seek, such as compressed files.
+pub fn stream_as_kml<R: Read + Seek>(
+    mut reader: R,
+    writer: impl Write,
+    options: WriteAsKmlOptions,
+) -> Result<(), Error> {
+    let static_cuas_location = match options.static_cuas_location {
+        Some(location) => location,
+        None => {
+            let location = read_static_cuas_location(&mut reader)?;
+            reader.rewind()?;
+            location
+        }
+    };
+    write_stream(reader, writer, &options, static_cuas_location)
+}
+
+/// Like [`stream_as_kml`], but reads the input from `open` instead of seeking back to its start.
+///
+/// `open` is called once for each time the input is read: once if
+/// [`WriteAsKmlOptions::static_cuas_location`] is set, twice otherwise. This allows converting
+/// inputs that are decompressed as they are read, by opening and wrapping them in
+/// [`decompress`](crate::decompress) again:
+///
+/// ```no_run
+/// # use std::{fs::File, io::BufReader};
+/// # use track2kml::{decompress, stream_as_kml_reopening, WriteAsKmlOptions};
+/// let writer = File::create("recording.kml")?;
+/// stream_as_kml_reopening(
+///     || Ok(decompress(BufReader::new(File::open("recording.json.zst")?))?),
+///     writer,
+///     WriteAsKmlOptions::default(),
+/// )?;
+/// # Ok::<(), track2kml::Error>(())
+/// ```
+pub fn stream_as_kml_reopening<R: Read>(
+    mut open: impl FnMut() -> Result<R, Error>,
+    writer: impl Write,
+    options: WriteAsKmlOptions,
+) -> Result<(), Error> {
+    let static_cuas_location = match options.static_cuas_location {
+        Some(location) => location,
+        None => read_static_cuas_location(open()?)?,
+    };
+    write_stream(open()?, writer, &options, static_cuas_location)
+}
+
+fn write_stream(
+    reader: impl Read,
+    writer: impl Write,
+    options: &WriteAsKmlOptions,
+    static_cuas_location: Position3d,
+) -> Result<(), Error> {
+    check_cuas_location(static_cuas_location)?;
+
+    let mut xml = Writer::new(writer);
+    write_document_start(&mut xml, &header_document(options))?;
+
+    let mut state = StreamState {
+        xml: &mut xml,
+        static_cuas_location,
+        cuas_range: options.cuas_range,
+        angle_points: options.angle_points,
+        error: None,
+    };
+    let mut de = serde_json::Deserializer::from_reader(reader);
+    let result = de
+        .deserialize_map(DocumentVisitor(&mut state))
+        .and_then(|()| de.end());
+    // Errors raised while writing are more relevant than the deserialization error they cause
+    if let Some(err) = state.error {
+        return Err(err);
+    }
+    result?;
+
+    write_feature(
+        &mut xml,
+        &Feature::Placemark(cuas_origin_placemark(static_cuas_location)),
+    )?;
+    write_document_end(&mut xml)?;
+
+    Ok(())
+}
+
+fn read_static_cuas_location(reader: impl Read) -> Result<Position3d, Error> {
+    // Other members are skipped without being stored
+    #[derive(Deserialize)]
+    struct StaticCuasLocation {
+        static_cuas_location: Position3d,
+    }
+
+    Ok(serde_json::from_reader::<_, StaticCuasLocation>(reader)?.static_cuas_location)
+}
+
+struct StreamState<'a, W: Write> {
+    xml: &'a mut Writer<W>,
+    static_cuas_location: Position3d,
+    cuas_range: f64,
+    angle_points: bool,
+    /// The error that caused deserialization to stop, if it did not come from the deserializer.
+    error: Option<Error>,
+}
+
+impl<W: Write> StreamState<'_, W> {
+    fn write<E: de::Error>(
+        &mut self,
+        f: impl FnOnce(&mut Writer<W>) -> Result<(), Error>,
+    ) -> Result<(), E> {
+        f(self.xml).map_err(|err| {
+            let message = err.to_string();
+            self.error = Some(err);
+            E::custom(message)
+        })
+    }
+}
+
+struct DocumentVisitor<'s, 'a, W: Write>(&'s mut StreamState<'a, W>);
+
+impl<'de, W: Write> Visitor<'de> for DocumentVisitor<'_, '_, W> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a COURAGEOUS document")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "detection" => map.next_value_seed(SetVisitor {
+                    state: &mut *self.0,
+                    kind: SetKind::Detection,
+                })?,
+                "tracks" => map.next_value_seed(SetVisitor {
+                    state: &mut *self.0,
+                    kind: SetKind::Tracks,
+                })?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum SetKind {
+    Detection,
+    Tracks,
+}
+
+/// Deserializes the `detection` or `tracks` array of a document, writing each element as soon as
+/// it has been read.
+struct SetVisitor<'s, 'a, W: Write> {
+    state: &'s mut StreamState<'a, W>,
+    kind: SetKind,
+}
+
+impl<'de, W: Write> DeserializeSeed<'de> for SetVisitor<'_, '_, W> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, W: Write> Visitor<'de> for SetVisitor<'_, '_, W> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            SetKind::Detection => f.write_str("a list of detection sets"),
+            SetKind::Tracks => f.write_str("a list of tracks"),
+        }
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let state = self.state;
+        let (static_cuas_location, cuas_range, angle_points) = (
+            state.static_cuas_location,
+            state.cuas_range,
+            state.angle_points,
+        );
+        match self.kind {
+            SetKind::Detection => {
+                state.write(|x| write_folder_start(x, "Detection Sets"))?;
+                let mut idx = 0;
+                while let Some(detection) = seq.next_element::<Detection>()? {
+                    state.write(|x| {
+                        let folder = detection_folder(
+                            &detection,
+                            RecordSet::Detection(idx),
+                            static_cuas_location,
+                            cuas_range,
+                            angle_points,
+                        )?;
+                        write_feature(x, &Feature::Folder(folder))
+                    })?;
+                    idx += 1;
+                }
+            }
+            SetKind::Tracks => {
+                state.write(|x| write_folder_start(x, "Tracks"))?;
+                let mut idx = 0;
+                while let Some(track) = seq.next_element::<Track>()? {
+                    state.write(|x| {
+                        for folder in
+                            classified_track_folders(&track, idx, static_cuas_location, cuas_range)?
+                        {
+                            write_feature(x, &Feature::Folder(folder))?;
+                        }
+                        Ok(())
+                    })?;
+                    idx += 1;
+                }
+            }
+        }
+        state.write(write_folder_end)
+    }
+}
@@ -1,259 +1,200 @@
-use courageous_format::{Arc, Classification, Location, Position3d, Track, TrackingRecord};
-use quick_xml::{events::BytesText, Writer};
-use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use courageous_format::{Classification, Location, Position3d, Track, TrackingRecord};
 
 use super::{
-    ext_data::{write_gxtrack_extended_data, write_tracking_extended_data},
-    geometry::{
-        create_arc_polygon, point_from_bearing_elevation_distance, ray_from_bearing,
-        ray_from_bearing_elevation,
+    ext_data::{gxtrack_extended_data, tracking_extended_data},
+    geometry::relative_location_geometry,
+    model::{
+        AltitudeMode, Feature, Folder, Geometry, GxTrack, MultiTrack, Placemark, TimePrimitive,
     },
-    uav_home_location::write_uav_home_location,
+    uav_home_location::uav_home_location_placemark,
+};
+use crate::{
+    record::{check_record_position, classification_str, record_time},
+    Error, RecordSet,
 };
 
-pub fn write_track_set(
-    x: &mut Writer<impl std::io::Write>,
+pub fn track_set_folder(
     set: &[Track],
     static_cuas_origin: Position3d,
     cuas_range: f64,
-) -> Result<(), quick_xml::Error> {
-    x.create_element("Folder").write_inner_content(|x| {
-        x.create_element("name")
-            .write_text_content(BytesText::new("Tracks"))?;
-
-        for track in set {
-            let filter_track_with_classification =
-                |mut track: Track, classification: Classification| {
-                    track.records.retain(|r| r.classification == classification);
-                    let name = track
-                        .name
-                        .get_or_insert_with(|| format!("Unnamed track (UAS ID {})", track.uas_id));
-                    match classification {
-                        Classification::Unknown => *name += " (Unknown)",
-                        Classification::Uav => *name += " (UAV)",
-                        Classification::Gcs => *name += " (GCS)",
-                        Classification::Other => *name += " (Other)",
-                    }
-
-                    track
-                };
-            const CLASSIFICATIONS: [Classification; 4] = [
-                Classification::Uav,
-                Classification::Gcs,
-                Classification::Other,
-                Classification::Unknown,
-            ];
-            for track in
-                CLASSIFICATIONS.map(|cls| filter_track_with_classification(track.clone(), cls))
-            {
-                if !track.records.is_empty() {
-                    write_track(x, &track, static_cuas_origin, cuas_range)?;
-                }
-            }
-        }
-
-        Ok(())
-    })?;
-
-    Ok(())
+) -> Result<Folder, Error> {
+    let mut folder = Folder {
+        name: "Tracks".to_owned(),
+        ..Default::default()
+    };
+
+    for (idx, track) in set.iter().enumerate() {
+        folder.features.extend(
+            classified_track_folders(track, idx, static_cuas_origin, cuas_range)?
+                .into_iter()
+                .map(Feature::Folder),
+        );
+    }
+
+    Ok(folder)
 }
 
-pub fn write_track<W: std::io::Write>(
-    x: &mut Writer<W>,
+/// Creates one folder per set of records of `track` with the same classification.
+pub fn classified_track_folders(
     track: &Track,
+    idx: usize,
     static_cuas_origin: Position3d,
     cuas_range: f64,
-) -> Result<(), quick_xml::Error> {
-    x.create_element("Folder").write_inner_content(|x| {
-        x.create_element("name").write_text_content(BytesText::new(
-            track.name.as_deref().unwrap_or("Unnamed track"),
-        ))?;
-
-        let records = track.records.iter().peekable();
-
-        if let Some(uav_home_location) = track.uav_home_location {
-            write_uav_home_location(x, uav_home_location)?;
+) -> Result<Vec<Folder>, Error> {
+    const CLASSIFICATIONS: [Classification; 4] = [
+        Classification::Uav,
+        Classification::Gcs,
+        Classification::Other,
+        Classification::Unknown,
+    ];
+    let mut buckets: [Vec<&TrackingRecord>; 4] = Default::default();
+    for record in &track.records {
+        let bucket = match record.classification {
+            Classification::Uav => 0,
+            Classification::Gcs => 1,
+            Classification::Other => 2,
+            Classification::Unknown => 3,
         };
+        buckets[bucket].push(record);
+    }
+
+    let base_name = track
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("Unnamed track (UAS ID {})", track.uas_id));
+    CLASSIFICATIONS
+        .iter()
+        .zip(buckets)
+        .filter(|(_, records)| !records.is_empty())
+        .map(|(&cls, records)| {
+            let mut folder = track_folder(
+                &format!("{} ({})", base_name, classification_str(cls)),
+                track.uav_home_location,
+                &records,
+                RecordSet::Track(idx),
+                static_cuas_origin,
+                cuas_range,
+            )?;
+            folder.description = Some(format!("UAV unique ID: {}", track.uas_id));
+            Ok(folder)
+        })
+        .collect()
+}
 
-        for record in records {
-            if matches!(
-                record.location,
-                Location::Position2d(_) | Location::Position3d(_)
-            ) {
-                continue;
-            }
-
-            x.create_element("Placemark").write_inner_content(|x| {
-                x.create_element("name").write_text_content(BytesText::new(
-                    track.name.as_deref().unwrap_or("Unnamed track"),
-                ))?;
-
-                let time_str =
-                    OffsetDateTime::from_unix_timestamp_nanos(record.time as i128 * 1_000_000)
-                        .unwrap()
-                        .format(&Rfc3339)
-                        .unwrap();
-
-                x.create_element("TimeStamp").write_inner_content(|x| {
-                    x.create_element("when")
-                        .write_text_content(BytesText::new(&time_str))?;
-
-                    Ok(())
-                })?;
+pub fn track_folder(
+    name: &str,
+    uav_home_location: Option<Position3d>,
+    records: &[&TrackingRecord],
+    set: RecordSet,
+    static_cuas_origin: Position3d,
+    cuas_range: f64,
+) -> Result<Folder, Error> {
+    let mut folder = Folder {
+        name: name.to_owned(),
+        ..Default::default()
+    };
+
+    if let Some(uav_home_location) = uav_home_location {
+        folder
+            .features
+            .push(Feature::Placemark(uav_home_location_placemark(
+                uav_home_location,
+            )));
+    };
+
+    for record in records {
+        // Positions are processed on the next step
+        let Some(geometry) = relative_location_geometry(
+            record.location,
+            record.cuas_location.unwrap_or(static_cuas_origin),
+            static_cuas_origin,
+            cuas_range,
+        ) else {
+            continue;
+        };
 
-                match record.location {
-                    Location::BearingElevationDistance {
-                        bearing,
-                        elevation,
-                        distance,
-                    } => {
-                        point_from_bearing_elevation_distance(
-                            x,
-                            record.cuas_location.unwrap_or(static_cuas_origin),
-                            bearing,
-                            elevation,
-                            distance,
-                        )?;
-                    }
-                    Location::BearingElevation { bearing, elevation } => {
-                        ray_from_bearing_elevation(
-                            x,
-                            record.cuas_location.unwrap_or(static_cuas_origin),
-                            bearing,
-                            elevation,
-                            cuas_range,
-                        )?;
-                    }
-                    Location::Bearing { bearing } => {
-                        ray_from_bearing(
-                            x,
-                            record.cuas_location.unwrap_or(static_cuas_origin),
-                            bearing,
-                            cuas_range,
-                        )?;
-                    }
-                    Location::Quad { quad } => {
-                        let (bearing_from, bearing_to) = match quad {
-                            courageous_format::Quad::North => (45., -45.),
-                            courageous_format::Quad::East => (135., 45.),
-                            courageous_format::Quad::South => (225., 135.),
-                            courageous_format::Quad::West => (315., 225.),
-                        };
-                        create_arc_polygon(
-                            x,
-                            bearing_from,
-                            bearing_to,
-                            static_cuas_origin,
-                            cuas_range,
-                        )?;
-                    }
-                    Location::Arc(Arc { from, to }) => {
-                        create_arc_polygon(x, from, to, static_cuas_origin, cuas_range)?;
-                    }
-                    // Positions are processed on the next step
-                    Location::Position2d(_) | Location::Position3d(_) => unreachable!(),
-                }
-                write_tracking_extended_data(x, record)?;
-                Ok(())
-            })?;
+        folder.features.push(Feature::Placemark(Placemark {
+            name: Some(name.to_owned()),
+            time: Some(TimePrimitive::TimeStamp(record_time(
+                record.time,
+                set,
+                record.record_number,
+            )?)),
+            geometry: Some(geometry),
+            extended_data: Some(tracking_extended_data(record)),
+            ..Default::default()
+        }));
+    }
+
+    // Group all point positions into a multitrack
+    // Place outside the MultiGeometry element because Google Earth doesn't seem to like tracks
+    // or MultiTracks inside MultiGeometry (It doesn't show them, and you cannot interact with
+    // them)
+    let mut multi_track = MultiTrack {
+        altitude_mode: AltitudeMode::Absolute,
+        tracks: Vec::new(),
+    };
+    let mut records = records.iter().copied().peekable();
+    while let Some(record) = records.next() {
+        if !matches!(
+            record.location,
+            Location::Position2d(_) | Location::Position3d(_)
+        ) {
+            continue;
         }
 
-        let pos_records = track.records.iter().filter(|record| {
-            matches!(
-                record.location,
-                Location::Position3d(_) | Location::Position2d(_)
-            )
-        });
-
-        // Group all point positions into a multitrack
-        // Place outside the MultiGeometry element because Google Earth doesn't seem to like tracks
-        // or MultiTracks inside MultiGeometry (It doesn't show them, and you cannot interact with
-        // them)
-        if pos_records.count() != 0 {
-            x.create_element("Placemark").write_inner_content(|x| {
-                x.create_element("styleUrl")
-                    .write_text_content(BytesText::new("track_style"))?;
-
-                x.create_element("name").write_text_content(BytesText::new(
-                    track.name.as_deref().unwrap_or("Unnamed track"),
-                ))?;
-
-                x.create_element("gx:MultiTrack").write_inner_content(|x| {
-                    x.create_element("altitudeMode")
-                        .write_text_content(BytesText::new("absolute"))?;
-                    let mut records = track.records.iter().peekable();
-                    while let Some(record) = records.next() {
-                        match record.location {
-                            Location::Position2d(_) | Location::Position3d(_) => {
-                                // HACK: This mostly assumes all records are either Position2d or Position3d
-                                let mut contiguous_pos_records = vec![record];
-                                while let Some(
-                                    &record @ TrackingRecord {
-                                        // HACK: This will result in points at the sea level if Position3d and Position2d are mixed
-                                        location: Location::Position3d(_) | Location::Position2d(_),
-                                        ..
-                                    },
-                                ) = records.peek()
-                                {
-                                    contiguous_pos_records.push(record);
-                                    records.next();
-                                }
-                                // Append all position records into a track
-                                x.create_element("gx:Track").write_inner_content(|x| {
-                                    x.create_element("altitudeMode").write_text_content(
-                                        BytesText::new(
-                                            if matches!(record.location, Location::Position3d(_)) {
-                                                "absolute"
-                                            } else {
-                                                "clampToGround"
-                                            },
-                                        ),
-                                    )?;
-                                    for record in contiguous_pos_records.iter() {
-                                        x.create_element("when").write_text_content(
-                                            BytesText::new(
-                                                &OffsetDateTime::from_unix_timestamp_nanos(
-                                                    record.time as i128 * 1_000_000,
-                                                )
-                                                .unwrap()
-                                                .format(&Rfc3339)
-                                                .unwrap(),
-                                            ),
-                                        )?;
-                                        let pos = match record.location {
-                                            Location::Position3d(pos) => pos,
-                                            Location::Position2d(pos) => Position3d {
-                                                lat: pos.lat,
-                                                lon: pos.lon,
-                                                height: 0.,
-                                            },
-                                            _ => unreachable!(),
-                                        };
-                                        x.create_element("gx:coord").write_text_content(
-                                            BytesText::new(&format!(
-                                                "{} {} {}",
-                                                pos.lon, pos.lat, pos.height
-                                            )),
-                                        )?;
-                                    }
-                                    write_gxtrack_extended_data(x, &contiguous_pos_records)?;
-
-                                    Ok(())
-                                })?;
-                            }
-                            _ => (),
-                        }
-                    }
-
-                    Ok(())
-                })?;
-
-                Ok(())
-            })?;
+        // HACK: This mostly assumes all records are either Position2d or Position3d
+        let mut contiguous_pos_records = vec![record];
+        while let Some(
+            &record @ TrackingRecord {
+                // HACK: This will result in points at the sea level if Position3d and Position2d are mixed
+                location: Location::Position3d(_) | Location::Position2d(_),
+                ..
+            },
+        ) = records.peek()
+        {
+            contiguous_pos_records.push(record);
+            records.next();
         }
 
-        Ok(())
-    })?;
-
-    Ok(())
+        // Append all position records into a track
+        let mut gx_track = GxTrack {
+            altitude_mode: if matches!(record.location, Location::Position3d(_)) {
+                AltitudeMode::Absolute
+            } else {
+                AltitudeMode::ClampToGround
+            },
+            extended_data: Some(gxtrack_extended_data(&contiguous_pos_records)),
+            ..Default::default()
+        };
+        for record in contiguous_pos_records.iter() {
+            let pos = match record.location {
+                Location::Position3d(pos) => pos,
+                Location::Position2d(pos) => Position3d {
+                    lat: pos.lat,
+                    lon: pos.lon,
+                    height: 0.,
+                },
+                _ => unreachable!(),
+            };
+            gx_track
+                .when
+                .push(record_time(record.time, set, record.record_number)?);
+            gx_track
+                .coords
+                .push(check_record_position(pos, set, record.record_number)?);
+        }
+        multi_track.tracks.push(gx_track);
+    }
+
+    if !multi_track.tracks.is_empty() {
+        folder.features.push(Feature::Placemark(Placemark {
+            name: Some(name.to_owned()),
+            style_url: Some("track_style".to_owned()),
+            geometry: Some(Geometry::MultiTrack(multi_track)),
+            ..Default::default()
+        }));
+    }
+
+    Ok(folder)
 }
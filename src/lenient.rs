@@ -0,0 +1,396 @@
+use std::{
+    fmt::Display,
+    io::{Read, Seek},
+    marker::PhantomData,
+};
+
+use courageous_format::{Detection, DetectionRecord, Location, Position3d, Track, TrackingRecord};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{
+    courageous::check_record,
+    document::{invalid_document, Reopening, Seekable, Source},
+    kml::geometry::distance_from_position,
+    schema::upgrade_document,
+    Database, Error, RecordSet, SchemaVersion,
+};
+
+/// A document read by [`read_document_lenient`], along with the records that had to be skipped.
+#[non_exhaustive]
+pub struct LenientDocument {
+    pub database: Database,
+    /// The schema version the document was detected as.
+    pub version: SchemaVersion,
+    pub skipped: Vec<SkippedRecord>,
+}
+
+/// A record that [`read_document_lenient`] could not read.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct SkippedRecord {
+    pub set: RecordSet,
+    /// Index of the record inside the `records` array of its set.
+    pub index: usize,
+    /// The record number, if it could be read.
+    pub record_number: Option<u64>,
+    /// The time of the record, in milliseconds since the UNIX epoch, if it could be read.
+    pub time: Option<u64>,
+    /// The last absolute position of the set before the record, if any.
+    pub last_position: Option<Position3d>,
+    /// Why the record was skipped.
+    pub message: String,
+}
+
+impl Display for SkippedRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.record_number {
+            Some(record_number) => write!(f, "record {}", record_number)?,
+            None => write!(f, "record at index {}", self.index)?,
+        }
+        write!(f, " of {}: {}", self.set, self.message)
+    }
+}
+
+/// Reads a COURAGEOUS document like [`read_document`](crate::read_document), but skipping the
+/// records that cannot be read instead of failing.
+///
+/// Each record is deserialized on its own, and skipped if that fails (e.g. because of a missing
+/// member or an unknown classification) or if its time, absolute position or CUAS location is
+/// invalid. Errors outside of records, such as invalid JSON syntax or a track without a UAS ID,
+/// still make the whole document fail. Compressed documents are accepted too.
+///
+/// The whole document is parsed into a [`Value`] before being converted, so this uses considerably
+/// more memory than [`read_document`](crate::read_document). Very large files that can be read
+/// without skipping records can be converted with [`stream_as_kml`](crate::stream_as_kml) instead.
+/// `reader` is only read again to find the text around JSON syntax errors. See
+/// [`read_document_lenient_reopening`] for inputs that cannot seek.
+pub fn read_document_lenient<R: Read + Seek>(reader: R) -> Result<LenientDocument, Error> {
+    read_source_lenient(&mut Seekable(reader))
+}
+
+/// Like [`read_document_lenient`], but reads the input from `open` instead of seeking back to its
+/// start, like [`read_document_reopening`](crate::read_document_reopening).
+pub fn read_document_lenient_reopening<R: Read>(
+    open: impl FnMut() -> Result<R, Error>,
+) -> Result<LenientDocument, Error> {
+    read_source_lenient(&mut Reopening(open, PhantomData))
+}
+
+fn read_source_lenient(source: &mut impl Source) -> Result<LenientDocument, Error> {
+    let result = serde_json::from_reader::<_, Value>(source.open()?);
+    let mut document = match result {
+        Ok(document) => document,
+        Err(err) if err.is_io() => return Err(Error::Io(err.into())),
+        Err(err) => {
+            return Err(invalid_document(
+                ".".to_owned(),
+                err,
+                SchemaVersion::CURRENT,
+                Some(&mut source.open()?),
+            ))
+        }
+    };
+    let version = upgrade_document(&mut document);
+
+    let detection_sets = take_array(&mut document, "detection");
+    let tracks = take_array(&mut document, "tracks");
+    let mut database: Database = deserialize_at(document, "", version)?;
+    let static_cuas_location = database.static_cuas_location;
+    let mut skipped = Vec::new();
+
+    for (idx, mut detection) in detection_sets.into_iter().enumerate() {
+        let set = RecordSet::Detection(idx);
+        let records = take_array(&mut detection, "records");
+        let mut detection: Detection =
+            deserialize_at(detection, &format!("detection[{}]", idx), version)?;
+        let mut last_position = None;
+        for (index, value) in records.into_iter().enumerate() {
+            match read_record::<DetectionRecord>(&value, set, static_cuas_location, |record| {
+                (
+                    record.record_number,
+                    record.time,
+                    record.location,
+                    record.cuas_location,
+                )
+            }) {
+                Ok((record, position)) => {
+                    last_position = position.or(last_position);
+                    detection.records.push(record);
+                }
+                Err(message) => {
+                    skipped.push(skipped_record(&value, set, index, last_position, message))
+                }
+            }
+        }
+        database.detection.push(detection);
+    }
+
+    for (idx, mut track) in tracks.into_iter().enumerate() {
+        let set = RecordSet::Track(idx);
+        let records = take_array(&mut track, "records");
+        let mut track: Track = deserialize_at(track, &format!("tracks[{}]", idx), version)?;
+        let mut last_position = None;
+        for (index, value) in records.into_iter().enumerate() {
+            match read_record::<TrackingRecord>(&value, set, static_cuas_location, |record| {
+                (
+                    record.record_number,
+                    record.time,
+                    Some(record.location),
+                    record.cuas_location,
+                )
+            }) {
+                Ok((record, position)) => {
+                    last_position = position.or(last_position);
+                    track.records.push(record);
+                }
+                Err(message) => {
+                    skipped.push(skipped_record(&value, set, index, last_position, message))
+                }
+            }
+        }
+        database.tracks.push(track);
+    }
+
+    Ok(LenientDocument {
+        database,
+        version,
+        skipped,
+    })
+}
+
+/// Replaces the array member `key` of `value` with an empty one, returning its elements. Other
+/// kinds of values are left in place, so that they fail to deserialize.
+fn take_array(value: &mut Value, key: &str) -> Vec<Value> {
+    match value.get_mut(key) {
+        Some(Value::Array(values)) => std::mem::take(values),
+        _ => Vec::new(),
+    }
+}
+
+/// Deserializes a value found at `path` inside the document.
+fn deserialize_at<T: DeserializeOwned>(
+    value: Value,
+    path: &str,
+    version: SchemaVersion,
+) -> Result<T, Error> {
+    serde_path_to_error::deserialize(value).map_err(|err| {
+        let inner_path = err.path().to_string();
+        let path = match (path, inner_path.as_str()) {
+            ("", inner_path) => inner_path.to_owned(),
+            (path, ".") => path.to_owned(),
+            (path, inner_path) => format!("{}.{}", path, inner_path),
+        };
+        invalid_document(path, err.into_inner(), version, None)
+    })
+}
+
+/// Deserializes and checks a record, returning why it is invalid otherwise. `fields` returns the
+/// members of the record that need to be checked.
+///
+/// The absolute position of the record is returned along with it, if it has one.
+fn read_record<T: DeserializeOwned>(
+    value: &Value,
+    set: RecordSet,
+    static_cuas_location: Position3d,
+    fields: impl FnOnce(&T) -> (u64, u64, Option<Location>, Option<Position3d>),
+) -> Result<(T, Option<Position3d>), String> {
+    let record: T = serde_path_to_error::deserialize(value).map_err(|err| err.to_string())?;
+    let (record_number, time, location, cuas_location) = fields(&record);
+    check_record(set, record_number, time, location, cuas_location).map_err(|err| match err {
+        Error::InvalidTimestamp { time, .. } => format!("invalid timestamp {}", time),
+        Error::InvalidCoordinate { position, .. } => format!(
+            "invalid coordinate (lat {}, lon {})",
+            position.lat, position.lon
+        ),
+        err => err.to_string(),
+    })?;
+    let position = location.and_then(|location| {
+        absolute_position(location, cuas_location.unwrap_or(static_cuas_location))
+    });
+    Ok((record, position))
+}
+
+fn skipped_record(
+    value: &Value,
+    set: RecordSet,
+    index: usize,
+    last_position: Option<Position3d>,
+    message: String,
+) -> SkippedRecord {
+    SkippedRecord {
+        set,
+        index,
+        record_number: value.get("record_number").and_then(Value::as_u64),
+        time: value.get("time").and_then(Value::as_u64),
+        last_position,
+        message,
+    }
+}
+
+fn absolute_position(location: Location, cuas_location: Position3d) -> Option<Position3d> {
+    match location {
+        Location::Position3d(pos) => Some(pos),
+        Location::Position2d(pos) => Some(Position3d {
+            lat: pos.lat,
+            lon: pos.lon,
+            height: 0.,
+        }),
+        Location::BearingElevationDistance {
+            bearing,
+            elevation,
+            distance,
+        } => Some(distance_from_position(
+            cuas_location,
+            bearing,
+            elevation,
+            distance,
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::schema::tests::v0_4_0_document;
+
+    /// Sets the members named `key` of every object inside `value`.
+    fn set_key(value: &mut Value, key: &str, new: &Value) {
+        match value {
+            Value::Object(members) => {
+                if let Some(member) = members.get_mut(key) {
+                    *member = new.clone();
+                }
+                members
+                    .values_mut()
+                    .for_each(|value| set_key(value, key, new));
+            }
+            Value::Array(values) => values.iter_mut().for_each(|value| set_key(value, key, new)),
+            _ => {}
+        }
+    }
+
+    fn diagnostic(err: Error) -> Box<crate::error::JsonDiagnostic> {
+        match err {
+            Error::InvalidDocument(diagnostic) => diagnostic,
+            err => panic!("expected a diagnostic, found {}", err),
+        }
+    }
+
+    #[test]
+    fn skip_invalid_records() {
+        let mut document = v0_4_0_document();
+        let records = document["tracks"][0]["records"].as_array_mut().unwrap();
+        let valid = records[1].clone();
+        records.truncate(1);
+        for record_number in 3..=7_u64 {
+            let mut record = valid.clone();
+            record["record_number"] = Value::from(record_number);
+            record["time"] = Value::from(1_682_935_200_000 + record_number * 1000);
+            records.push(record);
+        }
+        records[1]["classification"] = Value::from("Helicopter");
+        records[2].as_object_mut().unwrap().remove("time");
+        // What serializers write for NaN coordinates
+        set_key(&mut records[3]["location"], "lat", &Value::Null);
+        set_key(&mut records[4]["location"], "lat", &Value::from(95.));
+        let json = serde_json::to_vec(&document).unwrap();
+
+        let document = read_document_lenient(Cursor::new(&json)).unwrap();
+        assert_eq!(document.version, SchemaVersion::CURRENT);
+        assert_eq!(document.database.detection[0].records.len(), 1);
+        assert_eq!(
+            document.database.tracks[0]
+                .records
+                .iter()
+                .map(|record| record.record_number)
+                .collect::<Vec<_>>(),
+            [2, 7]
+        );
+
+        let skipped = &document.skipped;
+        assert_eq!(
+            skipped
+                .iter()
+                .map(|record| (record.set, record.index, record.record_number))
+                .collect::<Vec<_>>(),
+            [
+                (RecordSet::Track(0), 1, Some(3)),
+                (RecordSet::Track(0), 2, Some(4)),
+                (RecordSet::Track(0), 3, Some(5)),
+                (RecordSet::Track(0), 4, Some(6)),
+            ]
+        );
+        assert!(skipped[0].message.contains("classification"));
+        assert!(skipped[1].message.contains("time"));
+        assert_eq!(skipped[1].time, None);
+        assert_eq!(skipped[2].time, Some(1_682_935_205_000));
+        assert!(skipped[3].message.starts_with("invalid coordinate (lat 95"));
+        // The position of the only valid record before them
+        for record in skipped {
+            let position = record.last_position.unwrap();
+            assert_eq!((position.lat, position.lon), (51.451, 4.331));
+        }
+    }
+
+    #[test]
+    fn skipped_record_without_number() {
+        let mut document = v0_4_0_document();
+        document["detection"][0]["records"][0]
+            .as_object_mut()
+            .unwrap()
+            .remove("record_number");
+        let json = serde_json::to_vec(&document).unwrap();
+
+        let document = read_document_lenient(Cursor::new(&json)).unwrap();
+        let record = &document.skipped[0];
+        assert_eq!(
+            (record.set, record.index, record.record_number),
+            (RecordSet::Detection(0), 0, None)
+        );
+        assert_eq!(record.last_position.map(|pos| pos.lat), None);
+        assert!(record
+            .to_string()
+            .starts_with("record at index 0 of detection set #0: "));
+    }
+
+    #[test]
+    fn invalid_set() {
+        let mut document = v0_4_0_document();
+        document["tracks"][0]["uas_id"] = Value::from("seven");
+        let json = serde_json::to_vec(&document).unwrap();
+
+        let err = read_document_lenient(Cursor::new(&json)).err().unwrap();
+        assert_eq!(diagnostic(err).path, "tracks[0].uas_id");
+    }
+
+    #[test]
+    fn deserialize_at_paths() {
+        let track = v0_4_0_document()["tracks"][0].clone();
+        let mut invalid = track.clone();
+        invalid["uas_id"] = Value::Null;
+
+        let err = deserialize_at::<Track>(invalid.clone(), "tracks[3]", SchemaVersion::CURRENT);
+        assert_eq!(diagnostic(err.err().unwrap()).path, "tracks[3].uas_id");
+        let err = deserialize_at::<Track>(invalid, "", SchemaVersion::CURRENT);
+        assert_eq!(diagnostic(err.err().unwrap()).path, "uas_id");
+        // Errors in the value itself are found at `path`
+        let err = deserialize_at::<Track>(Value::from(3), "tracks[3]", SchemaVersion::V0_3_0);
+        let diagnostic = diagnostic(err.err().unwrap());
+        assert_eq!(diagnostic.path, "tracks[3]");
+        assert_eq!(diagnostic.version, SchemaVersion::V0_3_0);
+        assert!(deserialize_at::<Track>(track, "tracks[3]", SchemaVersion::CURRENT).is_ok());
+    }
+
+    #[test]
+    fn syntax_error() {
+        let json = "{\n  \"detection\": [],\n  \"tracks\": [}\n";
+
+        let diagnostic = diagnostic(read_document_lenient(Cursor::new(json)).err().unwrap());
+        assert_eq!(diagnostic.position, Some((3, 14)));
+        assert_eq!(diagnostic.snippet.as_deref(), Some("  \"tracks\": [}"));
+    }
+}
@@ -1,7 +1,65 @@
 pub use courageous_format::{
-    Arc, Classification, Detection, DetectionRecord, Document as Database, Location, Position2d,
-    Position3d, Quad, Track, TrackingRecord,
+    Alarm, Arc, Classification, Detection, DetectionRecord, Document as Database, Location,
+    Position2d, Position3d, Quad, Track, TrackingRecord, Velocity,
 };
 
-mod kml;
-pub use kml::{write_as_kml, WriteAsKmlOptions};
+mod art;
+pub use art::read_art_logs;
+
+mod compression;
+pub use compression::{decompress, detect_compression, Compression};
+
+mod cot;
+pub use cot::{cot_events, write_as_cot, write_cot_event, CotEvent, WriteAsCotOptions};
+
+mod courageous;
+pub use courageous::write_as_courageous;
+
+mod csv;
+pub use self::csv::{write_as_csv, WriteAsCsvOptions};
+
+mod czml;
+pub use czml::{write_as_czml, WriteAsCzmlOptions};
+
+mod datcon;
+pub use datcon::read_datcon_track;
+
+mod document;
+pub use document::{read_document, read_document_reopening};
+
+mod error;
+pub use error::{Error, JsonDiagnostic, RecordSet};
+
+mod gpx;
+pub use gpx::write_as_gpx;
+
+mod geojson;
+pub use geojson::{write_as_geojson, WriteAsGeoJsonOptions};
+
+mod hgh;
+pub use hgh::read_hgh_log;
+
+pub mod kml;
+mod lenient;
+pub use lenient::{
+    read_document_lenient, read_document_lenient_reopening, LenientDocument, SkippedRecord,
+};
+
+mod record;
+mod record_stream;
+pub use record_stream::{read_record_stream, RecordStream};
+
+mod xml;
+pub use kml::{
+    build_kml, build_many_kml, read_kml, stream_as_kml, stream_as_kml_reopening, write_as_kml,
+    write_as_kmz, write_kml, write_kmz, write_many_as_kml, WriteAsKmlOptions,
+};
+
+mod robin_radar;
+pub use robin_radar::read_robin_radar_gpx;
+
+mod schema;
+pub use schema::{detect_schema_version, migrate_document, SchemaVersion};
+
+mod senhive;
+pub use senhive::read_senhive_log;
@@ -0,0 +1,59 @@
+use courageous_format::{Classification, Position3d};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{Error, RecordSet};
+
+/// Converts the time of a record (In milliseconds since the UNIX epoch) into an [`OffsetDateTime`]
+/// that can be formatted as a RFC 3339 timestamp.
+pub(crate) fn record_time(
+    time: u64,
+    set: RecordSet,
+    record_number: u64,
+) -> Result<OffsetDateTime, Error> {
+    let invalid = || Error::InvalidTimestamp {
+        set,
+        record_number,
+        time,
+    };
+    let time = OffsetDateTime::from_unix_timestamp_nanos(time as i128 * 1_000_000)
+        .map_err(|_| invalid())?;
+    time.format(&Rfc3339).map_err(|_| invalid())?;
+    Ok(time)
+}
+
+pub(crate) fn is_valid_position(pos: Position3d) -> bool {
+    (-90. ..=90.).contains(&pos.lat) && (-180. ..=180.).contains(&pos.lon) && pos.height.is_finite()
+}
+
+pub(crate) fn check_cuas_location(pos: Position3d) -> Result<(), Error> {
+    if is_valid_position(pos) {
+        Ok(())
+    } else {
+        Err(Error::InvalidCuasLocation(pos))
+    }
+}
+
+pub(crate) fn check_record_position(
+    pos: Position3d,
+    set: RecordSet,
+    record_number: u64,
+) -> Result<Position3d, Error> {
+    if is_valid_position(pos) {
+        Ok(pos)
+    } else {
+        Err(Error::InvalidCoordinate {
+            set,
+            record_number,
+            position: pos,
+        })
+    }
+}
+
+pub(crate) fn classification_str(classification: Classification) -> &'static str {
+    match classification {
+        Classification::Gcs => "GCS",
+        Classification::Other => "Other",
+        Classification::Uav => "UAV",
+        Classification::Unknown => "Unknown",
+    }
+}
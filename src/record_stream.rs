@@ -0,0 +1,295 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read},
+};
+
+use courageous_format::{Detection, DetectionRecord, Position3d, Track, TrackingRecord};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{decompress, Database, Error};
+
+const FORMAT: &str = "COURAGEOUS JSONL";
+
+/// A document assembled by [`read_record_stream`].
+#[non_exhaustive]
+pub struct RecordStream {
+    pub database: Database,
+    /// Whether the last line of the stream was incomplete, and so ignored.
+    pub truncated: bool,
+}
+
+/// A line of a record stream. See [`read_record_stream`] for the members each line can have.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Line {
+    static_cuas_location: Option<Position3d>,
+    track: Option<u64>,
+    detection: Option<u64>,
+    name: Option<String>,
+    uav_home_location: Option<Position3d>,
+    record: Option<Value>,
+}
+
+/// Assembles a COURAGEOUS document from a JSON Lines stream of records, as written by sensors while
+/// they are running.
+///
+/// The first line is a header with the `static_cuas_location` of the document:
+/// ```json
+/// {"static_cuas_location": {"lat": 51.4507167, "lon": 4.3341194, "height": 15}}
+/// ```
+/// Every other line belongs to the track or detection set whose UAS ID is given by its `track` or
+/// `detection` member, and may have:
+/// - `record`: A tracking or detection record, added to the end of the set.
+/// - `name` and `uav_home_location`: Replace the members of the set with the same name.
+///
+/// ```json
+/// {"track": 3, "name": "DJI Mavic", "record": {"time": 1682935200000, "record_number": 1, ...}}
+/// {"detection": 7, "record": {"time": 1682935200100, "record_number": 2, ...}}
+/// ```
+///
+/// Tracks and detection sets are sorted by their first line. Empty lines are ignored, and so is the
+/// last line if it is incomplete, as happens when the logger writing the stream stops abruptly.
+/// Streams compressed with gzip or zstd are decompressed transparently.
+pub fn read_record_stream(reader: impl Read) -> Result<RecordStream, Error> {
+    let mut reader = decompress(BufReader::new(reader))?;
+    let mut static_cuas_location = None;
+    let mut tracks: Vec<Track> = Vec::new();
+    let mut detection: Vec<Detection> = Vec::new();
+    let mut track_indices = HashMap::new();
+    let mut detection_indices = HashMap::new();
+
+    let mut buf = Vec::new();
+    let mut line_number = 0;
+    let mut truncated = false;
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        line_number += 1;
+        let complete = buf.ends_with(b"\n");
+        if buf.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        let invalid = |message: String| Error::InvalidInput {
+            format: FORMAT,
+            message: format!("line {}: {}", line_number, message),
+        };
+
+        let line: Line = match serde_json::from_slice(&buf) {
+            Ok(line) => line,
+            Err(err) if err.is_eof() && !complete => {
+                truncated = true;
+                break;
+            }
+            Err(err) => return Err(invalid(err.to_string())),
+        };
+
+        if static_cuas_location.is_none() {
+            match line {
+                Line {
+                    static_cuas_location: Some(location),
+                    track: None,
+                    detection: None,
+                    name: None,
+                    uav_home_location: None,
+                    record: None,
+                } => static_cuas_location = Some(location),
+                _ => {
+                    return Err(invalid(
+                        "expected a header with only a static_cuas_location member".to_owned(),
+                    ))
+                }
+            }
+            continue;
+        }
+        if line.static_cuas_location.is_some() {
+            return Err(invalid(
+                "static_cuas_location can only be given in the header".to_owned(),
+            ));
+        }
+
+        match (line.track, line.detection) {
+            (Some(uas_id), None) => {
+                let idx = *track_indices.entry(uas_id).or_insert_with(|| {
+                    tracks.push(Track {
+                        uas_id,
+                        name: None,
+                        records: Vec::new(),
+                        uav_home_location: None,
+                    });
+                    tracks.len() - 1
+                });
+                let track = &mut tracks[idx];
+                if let Some(record) = line.record {
+                    track.records.push(
+                        TrackingRecord::deserialize(record)
+                            .map_err(|err| invalid(format!("invalid record: {}", err)))?,
+                    );
+                }
+                track.name = line.name.or(track.name.take());
+                track.uav_home_location = line.uav_home_location.or(track.uav_home_location);
+            }
+            (None, Some(uas_id)) => {
+                let idx = *detection_indices.entry(uas_id).or_insert_with(|| {
+                    detection.push(Detection {
+                        uas_id: Some(uas_id),
+                        name: None,
+                        records: Vec::new(),
+                        uav_home_location: None,
+                    });
+                    detection.len() - 1
+                });
+                let detection = &mut detection[idx];
+                if let Some(record) = line.record {
+                    detection.records.push(
+                        DetectionRecord::deserialize(record)
+                            .map_err(|err| invalid(format!("invalid record: {}", err)))?,
+                    );
+                }
+                detection.name = line.name.or(detection.name.take());
+                detection.uav_home_location =
+                    line.uav_home_location.or(detection.uav_home_location);
+            }
+            _ => {
+                return Err(invalid(
+                    "expected exactly one of the track and detection members".to_owned(),
+                ))
+            }
+        }
+    }
+
+    Ok(RecordStream {
+        database: Database {
+            detection,
+            tracks,
+            static_cuas_location: static_cuas_location.ok_or_else(|| Error::InvalidInput {
+                format: FORMAT,
+                message: "missing header line".to_owned(),
+            })?,
+        },
+        truncated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::schema::tests::v0_4_0_document;
+
+    /// Returns the header line and a tracking and detection record.
+    fn parts() -> (String, Value, Value) {
+        let document = v0_4_0_document();
+        let header = json!({ "static_cuas_location": document["static_cuas_location"] });
+        (
+            header.to_string(),
+            document["tracks"][0]["records"][0].clone(),
+            document["detection"][0]["records"][0].clone(),
+        )
+    }
+
+    fn error_message(stream: &str) -> String {
+        match read_record_stream(stream.as_bytes()) {
+            Err(Error::InvalidInput { message, .. }) => message,
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("invalid streams must fail"),
+        }
+    }
+
+    #[test]
+    fn assemble() {
+        let (header, tracking, detection) = parts();
+        let home = json!({"lat": 51.44, "lon": 4.32, "height": 5.});
+        let lines = [
+            header,
+            json!({"track": 7, "name": "First name", "record": tracking}).to_string(),
+            json!({"detection": 3, "record": detection}).to_string(),
+            String::new(),
+            json!({"track": 9, "record": tracking}).to_string(),
+            json!({"track": 7, "uav_home_location": home, "record": tracking}).to_string(),
+            json!({"track": 7, "name": "Second name"}).to_string(),
+            json!({"detection": 3, "name": "Detections"}).to_string(),
+        ];
+
+        let stream = read_record_stream(lines.join("\n").as_bytes()).unwrap();
+        assert!(!stream.truncated);
+        let database = stream.database;
+        assert_eq!(database.static_cuas_location.lat, 51.45);
+        assert_eq!(
+            database
+                .tracks
+                .iter()
+                .map(|track| (track.uas_id, track.records.len()))
+                .collect::<Vec<_>>(),
+            [(7, 2), (9, 1)]
+        );
+        let track = &database.tracks[0];
+        assert_eq!(track.name.as_deref(), Some("Second name"));
+        assert_eq!(track.uav_home_location.map(|pos| pos.lat), Some(51.44));
+        assert_eq!(database.tracks[1].name, None);
+        let detection = &database.detection[0];
+        assert_eq!(detection.uas_id, Some(3));
+        assert_eq!(detection.name.as_deref(), Some("Detections"));
+        assert_eq!(detection.records.len(), 1);
+    }
+
+    #[test]
+    fn truncated_last_line() {
+        let (header, tracking, _) = parts();
+        let line = json!({"track": 7, "record": tracking}).to_string();
+        let stream = format!("{}\n{}\n{}", header, line, &line[..line.len() / 2]);
+
+        let stream = read_record_stream(stream.as_bytes()).unwrap();
+        assert!(stream.truncated);
+        assert_eq!(stream.database.tracks[0].records.len(), 1);
+
+        // Only the last line may be incomplete
+        let stream = format!("{}\n{}\n{}\n", header, &line[..line.len() / 2], line);
+        assert!(error_message(&stream).starts_with("line 2: "));
+        // A complete last line does not need a line break
+        let stream = format!("{}\n{}", header, line);
+        let stream = read_record_stream(stream.as_bytes()).unwrap();
+        assert!(!stream.truncated);
+        assert_eq!(stream.database.tracks[0].records.len(), 1);
+    }
+
+    #[test]
+    fn invalid_header() {
+        let (header, tracking, _) = parts();
+        let line = json!({"track": 7, "record": tracking}).to_string();
+
+        assert_eq!(error_message(""), "missing header line");
+        assert_eq!(
+            error_message(&line),
+            "line 1: expected a header with only a static_cuas_location member"
+        );
+        let mut extra = serde_json::from_str::<Value>(&header).unwrap();
+        extra["track"] = Value::from(7);
+        assert_eq!(
+            error_message(&extra.to_string()),
+            "line 1: expected a header with only a static_cuas_location member"
+        );
+        assert_eq!(
+            error_message(&format!("{}\n{}", header, header)),
+            "line 2: static_cuas_location can only be given in the header"
+        );
+    }
+
+    #[test]
+    fn track_and_detection() {
+        let (header, tracking, _) = parts();
+        for line in [
+            json!({"track": 7, "detection": 7, "record": tracking}),
+            json!({"record": tracking}),
+            json!({"name": "No set"}),
+        ] {
+            assert_eq!(
+                error_message(&format!("{}\n{}", header, line)),
+                "line 2: expected exactly one of the track and detection members"
+            );
+        }
+    }
+}
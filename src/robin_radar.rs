@@ -0,0 +1,231 @@
+use std::io::BufRead;
+
+use courageous_format::{
+    Alarm, Classification, Location, Position2d, Position3d, Track, TrackingRecord,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    xml::{read_element_tree, Element},
+    Database, Error,
+};
+
+const FORMAT: &str = "Robin Radar GPX";
+
+/// Converts a GPX file exported by a Robin Radar system into a [`Database`].
+///
+/// The schema of these files is not published, and no real export was available when this reader
+/// was written, so it assumes the structure below: Standard GPX 1.1, plus a `<number>` element in
+/// tracks and a `classification` element (In any namespace) in the `<extensions>` of points. Older
+/// exports are known to lack `<extensions>`. `testdata/robin_radar.gpx` is a sample of it.
+/// ```xml
+/// <gpx version="1.1" creator="Robin Radar Systems">
+///   <trk>
+///     <name>Track 1042</name> <!-- Optional -->
+///     <number>1042</number> <!-- Optional -->
+///     <trkseg>
+///       <trkpt lat="51.4507167" lon="4.3341194">
+///         <ele>32.5</ele> <!-- Optional -->
+///         <time>2023-05-01T10:00:00.250Z</time>
+///         <extensions> <!-- Optional -->
+///           <rr:classification>drone</rr:classification>
+///         </extensions>
+///       </trkpt>
+///     </trkseg>
+///   </trk>
+/// </gpx>
+/// ```
+///
+/// Every `<trk>` becomes a [`Track`] of `Position2d` or `Position3d` records, depending on whether
+/// its points have an `<ele>` element. The UAS ID of each track is taken from its `<number>` element,
+/// or its index if there is none, and records are numbered in the order they appear in the file.
+///
+/// Classifications are mapped ignoring case: `uav` and `drone` to [`Classification::Uav`], `gcs`
+/// and `unknown` to their equivalent classification, and every other class (e.g. `bird`) to
+/// [`Classification::Other`]. Points without one are classified as [`Classification::Unknown`].
+///
+/// GPX files do not store the location of the radar, so it must be given as
+/// `static_cuas_location`.
+pub fn read_robin_radar_gpx(
+    reader: impl BufRead,
+    static_cuas_location: Position3d,
+) -> Result<Database, Error> {
+    let root = read_element_tree(reader)?;
+    if root.name != "gpx" {
+        return Err(invalid(format!(
+            "expected a <gpx> root element, found <{}>",
+            root.name
+        )));
+    }
+
+    let mut record_number = 0;
+    let tracks = root
+        .children("trk")
+        .enumerate()
+        .map(|(idx, trk)| {
+            let uas_id = match trk.child_text("number") {
+                Some(number) => number
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid track number {:?}", number)))?,
+                None => idx as u64,
+            };
+            let records = trk
+                .children("trkseg")
+                .flat_map(|segment| segment.children("trkpt"))
+                .map(|point| {
+                    record_number += 1;
+                    tracking_record(point, record_number)
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(Track {
+                uas_id,
+                name: trk.child_text("name").map(str::to_owned),
+                records,
+                uav_home_location: None,
+            })
+        })
+        .collect::<Result<_, Error>>()?;
+
+    Ok(Database {
+        detection: Vec::new(),
+        tracks,
+        static_cuas_location,
+    })
+}
+
+fn tracking_record(point: &Element, record_number: u64) -> Result<TrackingRecord, Error> {
+    let coordinate = |name| {
+        let value = point
+            .attribute(name)
+            .ok_or_else(|| invalid(format!("<trkpt> has no {} attribute", name)))?;
+        value
+            .parse::<f64>()
+            .map_err(|_| invalid(format!("invalid {} {:?}", name, value)))
+    };
+    let (lat, lon) = (coordinate("lat")?, coordinate("lon")?);
+    let location = match point.child_text("ele") {
+        Some(ele) => Location::Position3d(Position3d {
+            lat,
+            lon,
+            height: ele
+                .parse()
+                .map_err(|_| invalid(format!("invalid elevation {:?}", ele)))?,
+        }),
+        None => Location::Position2d(Position2d { lat, lon }),
+    };
+
+    let time = point
+        .child_text("time")
+        .ok_or_else(|| invalid("<trkpt> has no <time> element"))?;
+    let time = OffsetDateTime::parse(time, &Rfc3339)
+        .map_err(|err| invalid(format!("invalid time {:?}: {}", time, err)))?;
+    let time = u64::try_from(time.unix_timestamp_nanos() / 1_000_000)
+        .map_err(|_| invalid(format!("time {} is before the UNIX epoch", time)))?;
+
+    // Files exported before the extensions were added do not have them
+    let classification = point
+        .child("extensions")
+        .and_then(|extensions| {
+            extensions
+                .children
+                .iter()
+                .find(|extension| extension.name.rsplit(':').next() == Some("classification"))
+        })
+        .map_or(Classification::Unknown, |class| {
+            match class.text.trim().to_lowercase().as_str() {
+                "uav" | "drone" => Classification::Uav,
+                "gcs" => Classification::Gcs,
+                "unknown" => Classification::Unknown,
+                _ => Classification::Other,
+            }
+        });
+
+    Ok(TrackingRecord {
+        time,
+        record_number,
+        classification,
+        alarm: Alarm {
+            active: false,
+            certainty: 0.,
+        },
+        location,
+        identification: None,
+        velocity: None,
+        cuas_location: None,
+    })
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidInput {
+        format: FORMAT,
+        message: message.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATIC_CUAS_LOCATION: Position3d = Position3d {
+        lat: 51.45,
+        lon: 4.33,
+        height: 15.,
+    };
+
+    #[test]
+    fn sample() {
+        let database = read_robin_radar_gpx(
+            include_str!("../testdata/robin_radar.gpx").as_bytes(),
+            STATIC_CUAS_LOCATION,
+        )
+        .unwrap();
+
+        assert!(database.detection.is_empty());
+        assert_eq!(database.static_cuas_location.lat, STATIC_CUAS_LOCATION.lat);
+        assert_eq!(database.tracks.len(), 2);
+
+        let track = &database.tracks[0];
+        assert_eq!(track.uas_id, 1042);
+        assert_eq!(track.name.as_deref(), Some("Track 1042"));
+        assert_eq!(
+            track
+                .records
+                .iter()
+                .map(|record| (record.record_number, record.time, record.classification))
+                .collect::<Vec<_>>(),
+            [
+                (1, 1_682_935_200_250, Classification::Uav),
+                (2, 1_682_935_201_000, Classification::Other),
+                (3, 1_682_935_202_000, Classification::Gcs),
+            ]
+        );
+        let Location::Position3d(pos) = track.records[0].location else {
+            panic!("points with an elevation must be read as Position3d");
+        };
+        assert_eq!(
+            (pos.lat, pos.lon, pos.height),
+            (51.4507167, 4.3341194, 32.5)
+        );
+
+        // Tracks without a number take their index as UAS ID
+        let track = &database.tracks[1];
+        assert_eq!(track.uas_id, 1);
+        assert_eq!(track.name, None);
+        let record = &track.records[0];
+        assert_eq!(record.record_number, 4);
+        assert_eq!(record.classification, Classification::Unknown);
+        let Location::Position2d(pos) = record.location else {
+            panic!("points without an elevation must be read as Position2d");
+        };
+        assert_eq!((pos.lat, pos.lon), (51.45, 4.33));
+    }
+
+    #[test]
+    fn invalid_number() {
+        let gpx = r#"<gpx><trk><number>first</number></trk></gpx>"#;
+        assert!(matches!(
+            read_robin_radar_gpx(gpx.as_bytes(), STATIC_CUAS_LOCATION),
+            Err(Error::InvalidInput { .. })
+        ));
+    }
+}
@@ -0,0 +1,403 @@
+use std::{fmt::Display, io::Read};
+
+use serde::{
+    de::{DeserializeSeed, MapAccess, SeqAccess, Visitor},
+    Deserializer,
+};
+use serde_json::{Map, Value};
+
+use crate::{document::invalid_document, Database, Error};
+
+/// Versions of the COURAGEOUS schema that can be read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum SchemaVersion {
+    V0_3_0,
+    V0_3_1,
+    /// The version represented by [`Database`].
+    V0_4_0,
+}
+
+impl SchemaVersion {
+    pub const CURRENT: SchemaVersion = SchemaVersion::V0_4_0;
+}
+
+impl Display for SchemaVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaVersion::V0_3_0 => write!(f, "v0.3.0"),
+            SchemaVersion::V0_3_1 => write!(f, "v0.3.1"),
+            SchemaVersion::V0_4_0 => write!(f, "v0.4.0"),
+        }
+    }
+}
+
+/// Guesses the schema version of a COURAGEOUS document from its structure, since documents do not
+/// store it.
+///
+/// Documents with a `static_cuas_location` member or positions with a `height` member are v0.4.0.
+/// Otherwise, documents with record velocities are v0.3.1, and the rest v0.3.0. Note that a v0.3.1
+/// document without velocities cannot be told apart from a v0.3.0 one, but both are read the same
+/// way.
+pub fn detect_schema_version(document: &Value) -> SchemaVersion {
+    version_from_keys(
+        document.get("static_cuas_location").is_some(),
+        contains_key(document, "height"),
+        contains_key(document, "velocity"),
+    )
+}
+
+/// Like [`detect_schema_version`], but reading the document from `reader` without storing it.
+pub(crate) fn scan_schema_version(reader: impl Read) -> Result<SchemaVersion, serde_json::Error> {
+    let mut keys = ScannedKeys::default();
+    let mut de = serde_json::Deserializer::from_reader(reader);
+    KeyScanner {
+        keys: &mut keys,
+        top_level: true,
+    }
+    .deserialize(&mut de)?;
+    de.end()?;
+    Ok(version_from_keys(
+        keys.static_cuas_location,
+        keys.height,
+        keys.velocity,
+    ))
+}
+
+fn version_from_keys(static_cuas_location: bool, height: bool, velocity: bool) -> SchemaVersion {
+    if static_cuas_location || height {
+        SchemaVersion::V0_4_0
+    } else if velocity {
+        SchemaVersion::V0_3_1
+    } else {
+        SchemaVersion::V0_3_0
+    }
+}
+
+/// The members [`detect_schema_version`] looks for, found by [`KeyScanner`].
+#[derive(Default)]
+struct ScannedKeys {
+    /// Whether the document has a `static_cuas_location` member.
+    static_cuas_location: bool,
+    /// Whether any object has a `height` member.
+    height: bool,
+    /// Whether any object has a `velocity` member.
+    velocity: bool,
+}
+
+/// Visits every value of a document, recording which of the members in [`ScannedKeys`] it has.
+struct KeyScanner<'a> {
+    keys: &'a mut ScannedKeys,
+    top_level: bool,
+}
+
+impl<'de> DeserializeSeed<'de> for KeyScanner<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for KeyScanner<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("any JSON value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while seq
+            .next_element_seed(KeyScanner {
+                keys: &mut *self.keys,
+                top_level: false,
+            })?
+            .is_some()
+        {}
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "static_cuas_location" if self.top_level => self.keys.static_cuas_location = true,
+                "height" => self.keys.height = true,
+                "velocity" => self.keys.velocity = true,
+                _ => {}
+            }
+            map.next_value_seed(KeyScanner {
+                keys: &mut *self.keys,
+                top_level: false,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts a COURAGEOUS document of any supported schema version into a [`Database`], returning
+/// the version it was detected as. Use [`read_document`](crate::read_document) to read it directly
+/// from a file instead.
+///
+/// Documents older than v0.4.0 are migrated by renaming the `height_amsl` member of positions to
+/// `height`. They do not store the location of the C-UAS, so `static_cuas_location` is set to
+/// latitude, longitude and height 0; it should be replaced by the real location if known.
+pub fn migrate_document(mut document: Value) -> Result<(Database, SchemaVersion), Error> {
+    let version = upgrade_document(&mut document);
+    let database = serde_path_to_error::deserialize(document)
+        .map_err(|err| invalid_document(err.path().to_string(), err.into_inner(), version, None))?;
+    Ok((database, version))
+}
+
+/// Migrates `document` to the current schema version in place, as described in
+/// [`migrate_document`], returning the version it was detected as.
+pub(crate) fn upgrade_document(document: &mut Value) -> SchemaVersion {
+    let version = detect_schema_version(document);
+    if version < SchemaVersion::V0_4_0 {
+        rename_key(document, "height_amsl", "height");
+        if let Value::Object(members) = document {
+            let mut origin = Map::new();
+            for coordinate in ["lat", "lon", "height"] {
+                origin.insert(coordinate.to_owned(), Value::from(0.));
+            }
+            members.insert("static_cuas_location".to_owned(), Value::Object(origin));
+        }
+    }
+    version
+}
+
+/// Returns whether any object inside `value` has a member named `key`.
+fn contains_key(value: &Value, key: &str) -> bool {
+    match value {
+        Value::Object(members) => {
+            members.contains_key(key) || members.values().any(|value| contains_key(value, key))
+        }
+        Value::Array(values) => values.iter().any(|value| contains_key(value, key)),
+        _ => false,
+    }
+}
+
+/// Renames the members named `from` of every object inside `value` to `to`.
+fn rename_key(value: &mut Value, from: &str, to: &str) {
+    match value {
+        Value::Object(members) => {
+            if let Some(member) = members.remove(from) {
+                members.insert(to.to_owned(), member);
+            }
+            members
+                .values_mut()
+                .for_each(|value| rename_key(value, from, to));
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| rename_key(value, from, to)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use courageous_format::{
+        Alarm, Classification, Detection, DetectionRecord, Location, Position3d, Track,
+        TrackingRecord, Velocity,
+    };
+
+    use super::*;
+
+    /// A v0.4.0 document with a detection set and a track, whose records have a height of 20 m and
+    /// the first of them a velocity.
+    pub(crate) fn v0_4_0_document() -> Value {
+        let position = Location::Position3d(Position3d {
+            lat: 51.451,
+            lon: 4.331,
+            height: 20.,
+        });
+        let tracking_record = |record_number, velocity| TrackingRecord {
+            time: 1_682_935_200_000 + record_number * 1000,
+            record_number,
+            classification: Classification::Uav,
+            alarm: Alarm {
+                active: false,
+                certainty: 0.,
+            },
+            location: position,
+            identification: None,
+            velocity,
+            cuas_location: None,
+        };
+        let database = Database {
+            detection: vec![Detection {
+                uas_id: None,
+                name: Some("Detections".to_owned()),
+                records: vec![DetectionRecord {
+                    time: 1_682_935_200_000,
+                    record_number: 1,
+                    classification: Classification::Unknown,
+                    alarm: None,
+                    location: Some(position),
+                    identification: None,
+                    velocity: None,
+                    cuas_location: None,
+                }],
+                uav_home_location: None,
+            }],
+            tracks: vec![Track {
+                uas_id: 7,
+                name: None,
+                records: vec![
+                    tracking_record(
+                        2,
+                        Some(Velocity {
+                            east: 1.,
+                            north: 2.,
+                            up: 0.,
+                        }),
+                    ),
+                    tracking_record(3, None),
+                ],
+                uav_home_location: None,
+            }],
+            static_cuas_location: Position3d {
+                lat: 51.45,
+                lon: 4.33,
+                height: 15.,
+            },
+        };
+        serde_json::to_value(database).unwrap()
+    }
+
+    /// Turns a document returned by [`v0_4_0_document`] into one of an older schema version.
+    pub(crate) fn downgrade(mut document: Value, version: SchemaVersion) -> Value {
+        document
+            .as_object_mut()
+            .unwrap()
+            .remove("static_cuas_location");
+        rename_key(&mut document, "height", "height_amsl");
+        if version == SchemaVersion::V0_3_0 {
+            remove_key(&mut document, "velocity");
+        }
+        document
+    }
+
+    fn remove_key(value: &mut Value, key: &str) {
+        match value {
+            Value::Object(members) => {
+                members.remove(key);
+                members
+                    .values_mut()
+                    .for_each(|value| remove_key(value, key));
+            }
+            Value::Array(values) => values.iter_mut().for_each(|value| remove_key(value, key)),
+            _ => {}
+        }
+    }
+
+    fn track_heights(database: &Database) -> Vec<f64> {
+        database.tracks[0]
+            .records
+            .iter()
+            .map(|record| match record.location {
+                Location::Position3d(pos) => pos.height,
+                _ => panic!("records must keep their Position3d location"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detect_versions() {
+        let document = v0_4_0_document();
+        assert_eq!(detect_schema_version(&document), SchemaVersion::V0_4_0);
+        assert_eq!(
+            detect_schema_version(&downgrade(document.clone(), SchemaVersion::V0_3_1)),
+            SchemaVersion::V0_3_1
+        );
+        assert_eq!(
+            detect_schema_version(&downgrade(document.clone(), SchemaVersion::V0_3_0)),
+            SchemaVersion::V0_3_0
+        );
+
+        // Heights are enough to tell v0.4.0 documents apart, even if they lack a CUAS location
+        let mut document = document;
+        document
+            .as_object_mut()
+            .unwrap()
+            .remove("static_cuas_location");
+        assert_eq!(detect_schema_version(&document), SchemaVersion::V0_4_0);
+    }
+
+    #[test]
+    fn scan_versions() {
+        for version in [
+            SchemaVersion::V0_3_0,
+            SchemaVersion::V0_3_1,
+            SchemaVersion::V0_4_0,
+        ] {
+            let mut document = v0_4_0_document();
+            if version < SchemaVersion::CURRENT {
+                document = downgrade(document, version);
+            }
+            let json = serde_json::to_vec(&document).unwrap();
+            assert_eq!(scan_schema_version(&json[..]).unwrap(), version);
+        }
+
+        // Only a top-level `static_cuas_location` counts
+        let json = br#"{"tracks": [{"static_cuas_location": null}]}"#;
+        assert_eq!(
+            scan_schema_version(&json[..]).unwrap(),
+            SchemaVersion::V0_3_0
+        );
+        assert!(scan_schema_version(&b"{} {}"[..]).is_err());
+    }
+
+    #[test]
+    fn migrate_older_versions() {
+        for version in [SchemaVersion::V0_3_0, SchemaVersion::V0_3_1] {
+            let document = downgrade(v0_4_0_document(), version);
+            let (database, detected) = migrate_document(document).unwrap();
+            assert_eq!(detected, version);
+            // `height_amsl` is renamed to `height`
+            assert_eq!(track_heights(&database), [20., 20.]);
+            let Some(Location::Position3d(pos)) = database.detection[0].records[0].location else {
+                panic!("records must keep their Position3d location");
+            };
+            assert_eq!(pos.height, 20.);
+            let origin = database.static_cuas_location;
+            assert_eq!((origin.lat, origin.lon, origin.height), (0., 0., 0.));
+            assert_eq!(
+                database.tracks[0].records[0].velocity.is_some(),
+                version == SchemaVersion::V0_3_1
+            );
+        }
+    }
+
+    #[test]
+    fn migrate_current_version() {
+        let (database, version) = migrate_document(v0_4_0_document()).unwrap();
+        assert_eq!(version, SchemaVersion::V0_4_0);
+        assert_eq!(database.static_cuas_location.lat, 51.45);
+        assert_eq!(track_heights(&database), [20., 20.]);
+    }
+}
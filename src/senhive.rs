@@ -0,0 +1,260 @@
+use std::io::BufRead;
+
+use courageous_format::{
+    Alarm, Classification, Detection, DetectionRecord, Location, Position2d, Position3d, Track,
+    TrackingRecord,
+};
+use serde::Deserialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{Database, Error};
+
+const FORMAT: &str = "Senhive log";
+
+/// Converts a detection log of a Senhive sensor network into a [`Database`].
+///
+/// The format of these logs is not published, and no real log was available when this reader was
+/// written, so it assumes the structure below: One JSON object per line (Empty lines are skipped),
+/// for each detection. `testdata/senhive.jsonl` is a sample of it.
+/// ```json
+/// {
+///   "timestamp": "2023-05-01T10:00:00.250Z",
+///   "track_id": 17,                    // Optional
+///   "classification": "drone",         // Optional
+///   "identification": "DJI Mavic 3",   // Optional
+///   "latitude": 51.4507167,            // Optional for detections without a track
+///   "longitude": 4.3341194,            // Optional for detections without a track
+///   "altitude": 32.5                   // Optional
+/// }
+/// ```
+///
+/// Detections with a `track_id` become the records of a [`Track`] with that UAS ID, in the order
+/// they first appear. The rest are placed into a single [`Detection`] set, where they may lack a
+/// position. Positions are `Position3d` if an `altitude` is given, and `Position2d` otherwise.
+/// Records are numbered in the order they appear in the log.
+///
+/// Classifications are mapped ignoring case: `uav` and `drone` to [`Classification::Uav`], `gcs`
+/// and `unknown` to their equivalent classification, and every other class (e.g. `bird`) to
+/// [`Classification::Other`]. Since the latter is lossy, the original class is kept in the
+/// identification of those records, after their identification if they have one (e.g.
+/// `Tx-12 (bird)`). Detections without a class are classified as [`Classification::Unknown`].
+///
+/// Senhive logs do not store the location of the sensors, so it must be given as
+/// `static_cuas_location`.
+pub fn read_senhive_log(
+    reader: impl BufRead,
+    static_cuas_location: Position3d,
+) -> Result<Database, Error> {
+    let mut tracks: Vec<Track> = Vec::new();
+    let mut detection = Detection {
+        uas_id: None,
+        name: Some("Senhive detections".to_owned()),
+        records: Vec::new(),
+        uav_home_location: None,
+    };
+
+    let mut record_number = 0;
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: LogEntry = serde_json::from_str(&line)
+            .map_err(|err| invalid(format!("line {}: {}", idx + 1, err)))?;
+        record_number += 1;
+
+        let time = OffsetDateTime::parse(&entry.timestamp, &Rfc3339).map_err(|err| {
+            invalid(format!(
+                "line {}: invalid timestamp {:?}: {}",
+                idx + 1,
+                entry.timestamp,
+                err
+            ))
+        })?;
+        let time = u64::try_from(time.unix_timestamp_nanos() / 1_000_000).map_err(|_| {
+            invalid(format!(
+                "line {}: time {} is before the UNIX epoch",
+                idx + 1,
+                time
+            ))
+        })?;
+        let location = match (entry.latitude, entry.longitude, entry.altitude) {
+            (Some(lat), Some(lon), Some(height)) => {
+                Some(Location::Position3d(Position3d { lat, lon, height }))
+            }
+            (Some(lat), Some(lon), None) => Some(Location::Position2d(Position2d { lat, lon })),
+            _ => None,
+        };
+        let (classification, identification) =
+            classification(entry.classification.as_deref(), entry.identification);
+
+        match entry.track_id {
+            Some(track_id) => {
+                let location = location.ok_or_else(|| {
+                    invalid(format!(
+                        "line {}: tracked detection has no position",
+                        idx + 1
+                    ))
+                })?;
+                let record = TrackingRecord {
+                    time,
+                    record_number,
+                    classification,
+                    alarm: Alarm {
+                        active: false,
+                        certainty: 0.,
+                    },
+                    location,
+                    identification,
+                    velocity: None,
+                    cuas_location: None,
+                };
+                match tracks.iter_mut().find(|track| track.uas_id == track_id) {
+                    Some(track) => track.records.push(record),
+                    None => tracks.push(Track {
+                        uas_id: track_id,
+                        name: None,
+                        records: vec![record],
+                        uav_home_location: None,
+                    }),
+                }
+            }
+            None => detection.records.push(DetectionRecord {
+                time,
+                record_number,
+                classification,
+                alarm: None,
+                location,
+                identification,
+                velocity: None,
+                cuas_location: None,
+            }),
+        }
+    }
+
+    Ok(Database {
+        detection: if detection.records.is_empty() {
+            Vec::new()
+        } else {
+            vec![detection]
+        },
+        tracks,
+        static_cuas_location,
+    })
+}
+
+#[derive(Deserialize)]
+struct LogEntry {
+    timestamp: String,
+    track_id: Option<u64>,
+    classification: Option<String>,
+    identification: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    altitude: Option<f64>,
+}
+
+/// Maps a Senhive class into a COURAGEOUS classification, keeping it in the identification if the
+/// classification cannot represent it.
+fn classification(
+    class: Option<&str>,
+    identification: Option<String>,
+) -> (Classification, Option<String>) {
+    let Some(class) = class else {
+        return (Classification::Unknown, identification);
+    };
+    match class.trim().to_lowercase().as_str() {
+        "uav" | "drone" => (Classification::Uav, identification),
+        "gcs" => (Classification::Gcs, identification),
+        "unknown" => (Classification::Unknown, identification),
+        _ => {
+            let identification = match identification {
+                Some(identification) => format!("{} ({})", identification, class),
+                None => class.to_owned(),
+            };
+            (Classification::Other, Some(identification))
+        }
+    }
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidInput {
+        format: FORMAT,
+        message: message.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATIC_CUAS_LOCATION: Position3d = Position3d {
+        lat: 51.45,
+        lon: 4.33,
+        height: 15.,
+    };
+
+    #[test]
+    fn sample() {
+        let database = read_senhive_log(
+            include_str!("../testdata/senhive.jsonl").as_bytes(),
+            STATIC_CUAS_LOCATION,
+        )
+        .unwrap();
+
+        assert_eq!(database.tracks.len(), 2);
+        let track = &database.tracks[0];
+        assert_eq!(track.uas_id, 17);
+        assert_eq!(
+            track
+                .records
+                .iter()
+                .map(|record| (record.record_number, record.time, record.classification))
+                .collect::<Vec<_>>(),
+            [
+                (1, 1_682_935_200_250, Classification::Uav),
+                (3, 1_682_935_201_250, Classification::Uav),
+            ]
+        );
+        assert_eq!(
+            track.records[0].identification.as_deref(),
+            Some("DJI Mavic 3")
+        );
+        let Location::Position3d(pos) = track.records[0].location else {
+            panic!("detections with an altitude must be read as Position3d");
+        };
+        assert_eq!(
+            (pos.lat, pos.lon, pos.height),
+            (51.4507167, 4.3341194, 32.5)
+        );
+
+        let track = &database.tracks[1];
+        assert_eq!(track.uas_id, 4);
+        let record = &track.records[0];
+        assert_eq!(record.classification, Classification::Other);
+        assert_eq!(record.identification.as_deref(), Some("bird"));
+        assert!(matches!(record.location, Location::Position2d(_)));
+
+        // Detections without a track
+        assert_eq!(database.detection.len(), 1);
+        let records = &database.detection[0].records;
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| (record.record_number, record.classification))
+                .collect::<Vec<_>>(),
+            [(2, Classification::Gcs), (5, Classification::Unknown)]
+        );
+        assert_eq!(records[0].identification.as_deref(), Some("RC-N1"));
+        assert!(records[0].location.is_none());
+    }
+
+    #[test]
+    fn tracked_detection_without_position() {
+        let log = r#"{"timestamp": "2023-05-01T10:00:00Z", "track_id": 1}"#;
+        assert!(matches!(
+            read_senhive_log(log.as_bytes(), STATIC_CUAS_LOCATION),
+            Err(Error::InvalidInput { .. })
+        ));
+    }
+}
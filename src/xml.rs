@@ -0,0 +1,112 @@
+use std::io::BufRead;
+
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+
+use crate::Error;
+
+/// A generic XML element, used as an intermediate step when parsing XML based formats.
+pub(crate) struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    /// Text and CDATA content, concatenated.
+    pub text: String,
+    pub children: Vec<Element>,
+}
+
+impl Element {
+    fn new(start: &BytesStart) -> Result<Element, Error> {
+        let attributes = start
+            .attributes()
+            .map(|attr| {
+                let attr = attr.map_err(quick_xml::Error::from)?;
+                Ok((
+                    utf8(attr.key.as_ref())?.to_owned(),
+                    attr.unescape_value()?.into_owned(),
+                ))
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Element {
+            name: utf8(start.name().as_ref())?.to_owned(),
+            attributes,
+            text: String::new(),
+            children: Vec::new(),
+        })
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    pub fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|child| child.text.trim())
+    }
+}
+
+/// Reads the whole document from `reader`, returning its root element.
+pub(crate) fn read_element_tree(reader: impl BufRead) -> Result<Element, Error> {
+    let mut reader = Reader::from_reader(reader);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut stack: Vec<Element> = Vec::new();
+    loop {
+        let finished = match reader.read_event_into(&mut buf)? {
+            Event::Start(start) => {
+                stack.push(Element::new(&start)?);
+                None
+            }
+            Event::Empty(start) => Some(Element::new(&start)?),
+            Event::End(end) => {
+                Some(
+                    stack
+                        .pop()
+                        .ok_or_else(|| quick_xml::Error::EndEventMismatch {
+                            expected: String::new(),
+                            found: String::from_utf8_lossy(end.name().as_ref()).into_owned(),
+                        })?,
+                )
+            }
+            Event::Text(text) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&text.unescape()?);
+                }
+                None
+            }
+            Event::CData(data) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(utf8(&data)?);
+                }
+                None
+            }
+            Event::Eof => {
+                return Err(quick_xml::Error::UnexpectedEof("root element".to_owned()).into())
+            }
+            _ => None,
+        };
+        if let Some(element) = finished {
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(element),
+                None => return Ok(element),
+            }
+        }
+        buf.clear();
+    }
+}
+
+fn utf8(bytes: &[u8]) -> Result<&str, Error> {
+    Ok(std::str::from_utf8(bytes).map_err(|err| quick_xml::Error::NonDecodable(Some(err)))?)
+}